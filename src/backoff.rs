@@ -0,0 +1,129 @@
+use std::time::{Duration, Instant};
+
+/// Retry schedule of a failing target
+///
+/// After a failed run the next attempt is delayed by one run period, and the
+/// delay doubles with every further failure in a row, up to a maximum. A
+/// successful run clears the delay.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Backoff {
+    /// Number of failed runs in a row
+    consecutive_failures: u32,
+    /// Number of failed runs since the daemon started
+    total_failures: u64,
+    /// Earliest time of the next attempt after a failure
+    retry_at: Option<Instant>,
+}
+
+impl Backoff {
+    /// Records a successful run and clears the delay
+    ///
+    /// # Returns
+    /// * `u32` - Number of failed runs in a row before this success
+    pub fn record_success(&mut self) -> u32 {
+        let failures = self.consecutive_failures;
+        self.consecutive_failures = 0;
+        self.retry_at = None;
+        failures
+    }
+
+    /// Records a failed run and schedules the next attempt
+    ///
+    /// # Arguments
+    /// * `now` - Time of the failure
+    /// * `period` - Run period of the daemon, the delay after the first failure
+    /// * `max_backoff` - Upper limit of the delay
+    ///
+    /// # Returns
+    /// * `Duration` - Delay until the next attempt
+    pub fn record_failure(
+        &mut self,
+        now: Instant,
+        period: Duration,
+        max_backoff: Duration,
+    ) -> Duration {
+        self.consecutive_failures += 1;
+        self.total_failures += 1;
+
+        let exponent = (self.consecutive_failures - 1).min(16);
+        let delay = period.saturating_mul(1 << exponent).min(max_backoff);
+        self.retry_at = Some(now + delay);
+        delay
+    }
+
+    /// Returns the time left until the next attempt, or `None` if the target
+    /// can run now
+    pub fn remaining(&self, now: Instant) -> Option<Duration> {
+        self.retry_at
+            .filter(|retry_at| *retry_at > now)
+            .map(|retry_at| retry_at - now)
+    }
+
+    /// Number of failed runs in a row
+    pub fn consecutive_failures(&self) -> u32 {
+        self.consecutive_failures
+    }
+
+    /// Number of failed runs since the daemon started
+    pub fn total_failures(&self) -> u64 {
+        self.total_failures
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_growth_and_cap() {
+        // 失敗のたびに待ち時間が倍になり、上限で止まる
+        let period = Duration::from_secs(60);
+        let max_backoff = Duration::from_secs(300);
+        let now = Instant::now();
+        let mut backoff = Backoff::default();
+        assert_eq!(backoff.remaining(now), None);
+
+        let delays: Vec<u64> = (0..5)
+            .map(|_| backoff.record_failure(now, period, max_backoff).as_secs())
+            .collect();
+        assert_eq!(delays, vec![60, 120, 240, 300, 300]);
+        assert_eq!(backoff.consecutive_failures(), 5);
+        assert_eq!(backoff.total_failures(), 5);
+
+        assert_eq!(backoff.remaining(now), Some(max_backoff));
+        assert_eq!(
+            backoff.remaining(now + Duration::from_secs(100)),
+            Some(Duration::from_secs(200))
+        );
+        assert_eq!(backoff.remaining(now + max_backoff), None);
+
+        // 長く失敗し続けてもオーバーフローしない
+        for _ in 0..100 {
+            backoff.record_failure(now, period, max_backoff);
+        }
+        assert_eq!(backoff.remaining(now), Some(max_backoff));
+    }
+
+    #[test]
+    fn test_backoff_reset_on_success() {
+        // 成功すると待ち時間と連続失敗数がリセットされる
+        let period = Duration::from_secs(10);
+        let max_backoff = Duration::from_secs(300);
+        let now = Instant::now();
+        let mut backoff = Backoff::default();
+
+        backoff.record_failure(now, period, max_backoff);
+        backoff.record_failure(now, period, max_backoff);
+        assert_eq!(backoff.record_success(), 2);
+        assert_eq!(backoff.remaining(now), None);
+        assert_eq!(backoff.consecutive_failures(), 0);
+        assert_eq!(backoff.total_failures(), 2);
+
+        // 次の失敗は再び 1 周期から始まる
+        assert_eq!(
+            backoff.record_failure(now, period, max_backoff),
+            Duration::from_secs(10)
+        );
+        assert_eq!(backoff.record_success(), 1);
+    }
+}
@@ -1,5 +1,6 @@
 pub mod config;
 pub mod daemon;
+pub mod fan;
 pub mod sample;
 pub mod status;
 mod utils;
@@ -1,12 +1,111 @@
 use std::thread;
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 
-use crate::config::TargetIlo;
+use crate::backoff::Backoff;
+use crate::config::{FailsafeConfig, IloConfig, TargetIlo, VerificationConfig};
 use crate::cputemp;
+use crate::failsafe::{check_reading, Failsafe, ReadingCheck};
+use crate::gen_ssh::{AppliedCommands, FanCurveState, PendingVerification, VerificationOutcome};
+use crate::redfish::RedfishClient;
 use crate::ssh;
 
+/// Lower limit in seconds of the SSH time limit of a target
+const MIN_SSH_TIMEOUT_SECONDS: u64 = 10;
+
+/// State of a target kept across daemon iterations
+#[derive(Default)]
+struct TargetState {
+    /// States of the fan curves, used for hysteresis and PID control
+    curves: Vec<FanCurveState>,
+    /// Retry schedule after failed runs
+    backoff: Backoff,
+    /// Trust in the readings and whether the failsafe is active
+    failsafe: Failsafe,
+    /// Names of the fans reported as failed by the previous run
+    failed_fans: Vec<String>,
+    /// Names of the sensors that needed attention in the previous run
+    warning_sensors: Vec<String>,
+    /// SSH session kept open across runs, with its time limit
+    ssh: Option<ssh::SshClient>,
+    /// Redfish client kept across runs, with its session and connections
+    redfish: Option<RedfishClient>,
+    /// Tokio runtime of the target, kept so the Redfish connections survive
+    /// between runs
+    runtime: Option<tokio::runtime::Runtime>,
+    /// Fan commands applied by the last successful run
+    applied: AppliedCommands,
+    /// Applied fan speeds to check against the readings of the next run
+    verification: PendingVerification,
+}
+
+impl TargetState {
+    /// Creates the state of a target before its first run
+    ///
+    /// # Arguments
+    /// * `target` - Target ILO configuration
+    /// * `ssh_timeout` - Time limit for connecting and for every SSH call
+    fn new(target: &TargetIlo, ssh_timeout: Duration) -> Self {
+        let mut client = ssh::SshClient::new(
+            target.host.clone(),
+            target.user.clone(),
+            target.password_base64.clone(),
+        );
+        client.set_timeout(ssh_timeout);
+
+        TargetState {
+            ssh: Some(client),
+            ..Default::default()
+        }
+    }
+
+    /// Takes the Tokio runtime of the target, creating it on first use
+    ///
+    /// Put the runtime back into `runtime` after use.
+    fn take_runtime(&mut self) -> Result<tokio::runtime::Runtime> {
+        match self.runtime.take() {
+            Some(runtime) => Ok(runtime),
+            None => Ok(tokio::runtime::Runtime::new()?),
+        }
+    }
+
+    /// Records a successful run and clears the backoff
+    fn record_success(&mut self, host: &str) {
+        let failures = self.backoff.record_success();
+        if failures > 0 {
+            info!("{} recovered after {} failed runs", host, failures);
+        }
+    }
+
+    /// Records a failed run and schedules the next attempt
+    ///
+    /// The delay does not grow beyond one run period while the failsafe is
+    /// pending, so it still trips and releases on time.
+    fn record_failure(
+        &mut self,
+        host: &str,
+        error: &anyhow::Error,
+        period: Duration,
+        max_backoff: Duration,
+    ) {
+        let max_backoff = self.failsafe.max_backoff(period, max_backoff);
+        let delay = self
+            .backoff
+            .record_failure(Instant::now(), period, max_backoff);
+
+        error!(
+            "Fan control of {} failed ({} in a row, {} in total), retrying in {} seconds: {}",
+            host,
+            self.backoff.consecutive_failures(),
+            self.backoff.total_failures(),
+            delay.as_secs(),
+            error
+        );
+    }
+}
+
 pub fn start_daemon(config_path: String) -> Result<()> {
     debug!("Starting daemon with config path: {}", config_path);
 
@@ -26,49 +125,191 @@ pub fn start_daemon(config_path: String) -> Result<()> {
 
     let running_interval = config.run_period_seconds;
 
+    // Keep the fan curve state of each target across iterations
+    let ssh_timeout = ssh_timeout(&config);
+    let mut states: Vec<TargetState> = config
+        .targets
+        .iter()
+        .map(|target| TargetState::new(target, ssh_timeout))
+        .collect();
+
     // Initialize the Tokio runtime
     // Run the daemon main function
     let rt = tokio::runtime::Runtime::new()?;
+
+    // Listen for shutdown signals in the background for the whole run
+    let mut shutdown = rt.spawn(wait_for_shutdown());
+
     loop {
         // Run the control function
-        rt.block_on(daemon_main(config.clone()))?;
+        rt.block_on(daemon_main(&config, &mut states));
 
-        // Sleep for the specified interval
+        // Sleep for the specified interval, waking up early on shutdown
         info!("Sleeping for {} seconds", running_interval);
-        std::thread::sleep(std::time::Duration::from_secs(running_interval as u64));
+        let shutdown_requested = rt.block_on(async {
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(running_interval as u64)) => false,
+                result = &mut shutdown => {
+                    if let Ok(Err(e)) = result {
+                        error!("Failed to listen for shutdown signals: {}", e);
+                    }
+                    true
+                }
+            }
+        });
+
+        if shutdown_requested {
+            info!("Shutdown requested, restoring fan settings");
+            restore_fans(&config, &mut states);
+            return Ok(());
+        }
+    }
+}
+
+/// Returns the time limit for connecting and for every SSH call of a target
+///
+/// Derived from the run period, so a hung iLO cannot hold up the control of
+/// the other targets for much longer than one run. The iLO is slow to
+/// answer, so the limit is never shorter than `MIN_SSH_TIMEOUT_SECONDS`.
+fn ssh_timeout(config: &IloConfig) -> Duration {
+    Duration::from_secs((config.run_period_seconds as u64).max(MIN_SSH_TIMEOUT_SECONDS))
+}
+
+/// Waits until the daemon is asked to stop
+///
+/// Resolves on Ctrl-C, and on Unix also on SIGTERM as sent by systemd.
+async fn wait_for_shutdown() -> Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate = signal(SignalKind::terminate())?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result?,
+            _ = terminate.recv() => {}
+        }
     }
+
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await?;
+
+    Ok(())
 }
 
-async fn daemon_main(config: crate::config::IloConfig) -> Result<()> {
+/// Lifts the fan caps of every target before the daemon exits
+///
+/// Failures are logged and do not stop the remaining targets from being
+/// restored.
+fn restore_fans(config: &IloConfig, states: &mut [TargetState]) {
+    thread::scope(|scope| {
+        for (target, state) in config.targets.iter().zip(states.iter_mut()) {
+            scope.spawn(move || {
+                let commands =
+                    crate::gen_ssh::generate_reset_commands(target, config.shutdown_fan_speed);
+                debug!("Fan reset commands for {}: {:?}", &target.host, &commands);
+
+                match exec_commands(target, state, commands) {
+                    Ok(_) => {
+                        info!("Restored fan settings of {}", &target.host);
+                    }
+                    Err(e) => {
+                        error!("Failed to restore fan settings of {}: {}", &target.host, e);
+                    }
+                }
+
+                if let Some(client) = state.ssh.as_mut() {
+                    client.disconnect();
+                }
+
+                if let Some(mut client) = state.redfish.take() {
+                    let result = state
+                        .take_runtime()
+                        .and_then(|runtime| runtime.block_on(client.logout()));
+                    if let Err(e) = result {
+                        warn!("Failed to log out of {}: {}", &target.host, e);
+                    }
+                }
+            });
+        }
+    });
+}
+
+async fn daemon_main(config: &IloConfig, states: &mut [TargetState]) {
     info!("Daemon main function started");
 
-    let mut handles = Vec::new();
+    let now = Instant::now();
 
-    for target in config.targets.iter() {
-        let target_clone = target.clone();
+    // Run every target that is not waiting for its retry in its own thread
+    let results: Vec<Option<Result<()>>> = thread::scope(|scope| {
+        let handles: Vec<_> = config
+            .targets
+            .iter()
+            .zip(states.iter_mut())
+            .map(|(target, state)| {
+                if let Some(remaining) = state.backoff.remaining(now) {
+                    debug!(
+                        "Skipping {} for another {} seconds after failures",
+                        &target.host,
+                        remaining.as_secs()
+                    );
+                    return None;
+                }
 
-        let handle = thread::spawn(move || {
-            // 各スレッド内で新しいランタイムを作成
-            let thread_rt =
-                tokio::runtime::Runtime::new().expect("Failed to create runtime in thread");
-            thread_rt.block_on(runner(target_clone))
-        });
+                Some(scope.spawn(move || {
+                    // 各ターゲットのランタイムを実行間で使い回す
+                    let thread_rt = state.take_runtime()?;
+                    let result = thread_rt.block_on(runner(target, config, state));
+                    state.runtime = Some(thread_rt);
+                    result
+                }))
+            })
+            .collect();
 
-        handles.push(handle);
-    }
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle.map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|_| Err(anyhow::anyhow!("Thread panicked")))
+                })
+            })
+            .collect()
+    });
+
+    // Record the outcome of each target, a failure only affects that target
+    let period = Duration::from_secs(config.run_period_seconds as u64);
+    let max_backoff = Duration::from_secs(config.max_backoff_seconds as u64);
+    let (mut succeeded, mut failed, mut skipped) = (0, 0, 0);
 
-    for handle in handles {
-        handle
-            .join()
-            .map_err(|_| anyhow::anyhow!("Thread panicked"))??;
+    for ((target, state), result) in config.targets.iter().zip(states.iter_mut()).zip(results) {
+        match result {
+            Some(Ok(())) => {
+                succeeded += 1;
+                state.record_success(&target.host);
+            }
+            Some(Err(e)) => {
+                failed += 1;
+                state.record_failure(&target.host, &e, period, max_backoff);
+            }
+            None => {
+                skipped += 1;
+            }
+        }
     }
 
-    info!("All hosts completed successfully");
-    Ok(())
+    if failed == 0 && skipped == 0 {
+        info!("All hosts completed successfully");
+    } else {
+        warn!(
+            "{} hosts succeeded, {} failed, {} waiting for retry",
+            succeeded, failed, skipped
+        );
+    }
 }
 
-async fn runner(config: TargetIlo) -> Result<()> {
-    let password = config.password_base64.clone();
+async fn runner(config: &TargetIlo, settings: &IloConfig, state: &mut TargetState) -> Result<()> {
+    let failsafe = &settings.failsafe;
     let host = config.host.clone();
     let user = config.user.clone();
 
@@ -76,47 +317,271 @@ async fn runner(config: TargetIlo) -> Result<()> {
     debug!("User: {}", &user);
 
     // Get the current temperature
-    let temprature = cputemp::get_temp_data(&host, &user, &password).await?;
+    let read_started = Instant::now();
+    let result = read_temp_data(config, state).await;
+    match &result {
+        Ok(_) => state.failsafe.record_read(read_started),
+        Err(_) => state.failsafe.record_read_failure(),
+    }
+
+    // Force the fans up when the readings can no longer be trusted
+    if let Some(reason) = state.failsafe.untrusted_reason(failsafe, Instant::now()) {
+        if let Err(failsafe_error) = enter_failsafe(config, failsafe, state, &reason) {
+            error!(
+                "Failed to apply failsafe fan speed to {}: {}",
+                &host, failsafe_error
+            );
+            if result.is_ok() {
+                return Err(failsafe_error);
+            }
+        }
+        return result.map(|_| ());
+    }
+    let temprature = result?;
 
-    info!(
-        "Current CPU 0 Temp of {}: {:?}°C",
-        &host, &temprature.cpu_temps[0].current
-    );
     debug!("Detail data of {}:\n {}", &host, &temprature);
 
-    let mut max_cpu_temp = 0;
+    check_failed_fans(config, &temprature, state);
+    check_sensors(config, &temprature, state);
 
-    temprature.cpu_temps.iter().for_each(|temp| {
-        if temp.current > max_cpu_temp {
-            max_cpu_temp = temp.current;
-        }
-    });
+    // The critical failsafe comes before checking the commands of the
+    // previous run, a failed check is reported once the fans are set
+    let outcome = match check_reading(
+        failsafe,
+        &settings.verification,
+        &temprature,
+        &mut state.verification,
+    ) {
+        ReadingCheck::Failsafe(reason) => return enter_failsafe(config, failsafe, state, &reason),
+        ReadingCheck::Control(outcome) => outcome,
+    };
+    let verified = verify_applied_speeds(config, &settings.verification, outcome, state);
 
-    // Generate fan commands based on the current temperature
-    let commands = crate::gen_ssh::generate_fan_commands(&config, max_cpu_temp);
+    apply_fan_curves(config, settings, &temprature, state)?;
+    verified
+}
+
+/// Applies the fan curves of a target to its current temperatures
+///
+/// Commands equal to the ones applied by a recent run are not sent again.
+fn apply_fan_curves(
+    config: &TargetIlo,
+    settings: &IloConfig,
+    temp_data: &cputemp::TempData,
+    state: &mut TargetState,
+) -> Result<()> {
+    let force_refresh = Duration::from_secs(settings.force_refresh_seconds as u64);
+    let host = &config.host;
+
+    // Generate fan commands based on the current temperature of each curve
+    let speeds = crate::gen_ssh::calculate_fan_speeds(config, temp_data, &mut state.curves)?;
+    let mut commands = crate::gen_ssh::generate_commands_from_speeds(&speeds);
     debug!("Fan control commands for {}: {:?}", &host, &commands);
 
+    if commands.is_empty() {
+        warn!(
+            "No fan curve of {} produced a fan speed, leaving fans unchanged",
+            &host
+        );
+        return Ok(());
+    }
+
+    // Lower the floors raised by the failsafe before applying the fan curves
+    if state.failsafe.is_active() {
+        let mut release_commands = crate::gen_ssh::generate_failsafe_release_commands(config);
+        release_commands.append(&mut commands);
+        commands = release_commands;
+    }
+
+    // Skip the SSH round trip when the fans are already set this way
+    if !state
+        .applied
+        .needs_sending(&commands, Instant::now(), force_refresh)
+    {
+        debug!("Fan speeds of {} are unchanged, skipping SSH", &host);
+        return Ok(());
+    }
+
     // Execute the fan control commands
-    let mut client = ssh::SshClient::new(host.clone(), user, password);
-    match client.connect() {
-        Ok(_) => {
-            debug!("Connected to {}", &host);
+    exec_commands(config, state, commands.clone())?;
+    state.applied.record(commands, Instant::now());
+    if settings.verification.enabled {
+        state.verification.expect(speeds);
+    }
+
+    if state.failsafe.release() {
+        info!("Failsafe of {} ended, fan curves are applied again", &host);
+    }
+
+    Ok(())
+}
+
+/// Compares the fan readings of a run with the fan speeds applied by the
+/// previous run
+///
+/// Fans that ignore the commands get them again on this run, up to
+/// `retries` times, before the target is reported as failed.
+fn verify_applied_speeds(
+    config: &TargetIlo,
+    verification: &VerificationConfig,
+    outcome: VerificationOutcome,
+    state: &mut TargetState,
+) -> Result<()> {
+    let details = |mismatches: Vec<crate::gen_ssh::FanMismatch>| {
+        mismatches
+            .iter()
+            .map(|mismatch| mismatch.to_string())
+            .collect::<Vec<String>>()
+            .join("; ")
+    };
+
+    match outcome {
+        VerificationOutcome::Verified => {
+            if verification.enabled {
+                debug!("Fan speeds of {} verified", &config.host);
+            }
+            Ok(())
         }
-        Err(e) => {
-            error!("Failed to connect to {}: {}", &host, e);
-            return Err(e);
+        VerificationOutcome::Resend(mismatches) => {
+            warn!(
+                "Fans of {} do not follow the fan commands, sending them again ({}/{}): {}",
+                &config.host,
+                state.verification.resends(),
+                verification.retries,
+                details(mismatches)
+            );
+            state.applied.clear();
+            Ok(())
+        }
+        VerificationOutcome::Failed(mismatches) => {
+            // Send the commands again once the target is retried
+            state.applied.clear();
+            Err(anyhow::anyhow!(
+                "Fans of {} ignore the fan commands, check that the iLO accepts them: {}",
+                &config.host,
+                details(mismatches)
+            ))
         }
     }
+}
 
-    match client.exec(commands) {
+/// Reads the temperatures of a target with the Redfish client of its state
+///
+/// The client is created on first use and kept for the following runs.
+async fn read_temp_data(config: &TargetIlo, state: &mut TargetState) -> Result<cputemp::TempData> {
+    if state.redfish.is_none() {
+        state.redfish = Some(RedfishClient::from_target(config)?);
+    }
+
+    let client = state
+        .redfish
+        .as_mut()
+        .expect("Redfish client was just created");
+    cputemp::get_temp_data(client).await
+}
+
+/// Alerts when fans of a target fail or recover
+fn check_failed_fans(config: &TargetIlo, temp_data: &cputemp::TempData, state: &mut TargetState) {
+    let failed_fans = temp_data.failed_fans();
+    let names: Vec<String> = failed_fans.iter().map(|fan| fan.name.clone()).collect();
+    if names == state.failed_fans {
+        return;
+    }
+
+    if names.is_empty() {
+        info!("All fans of {} are working again", &config.host);
+    } else {
+        let details: Vec<String> = failed_fans.iter().map(|fan| fan.to_string()).collect();
+        error!(
+            "Fan failure detected on {}: [{}], raising the remaining fans by {}%",
+            &config.host,
+            details.join("; "),
+            config.fan_failure_compensation
+        );
+    }
+    state.failed_fans = names;
+}
+
+/// Warns when sensors of a target reach a threshold or report a problem
+fn check_sensors(config: &TargetIlo, temp_data: &cputemp::TempData, state: &mut TargetState) {
+    let sensors = temp_data.sensors_needing_attention();
+    let names: Vec<String> = sensors.iter().map(|sensor| sensor.name.clone()).collect();
+    if names == state.warning_sensors {
+        return;
+    }
+
+    if names.is_empty() {
+        info!("All sensors of {} are back to normal", &config.host);
+    } else {
+        let details: Vec<String> = sensors.iter().map(|sensor| sensor.to_string()).collect();
+        warn!(
+            "Sensors of {} need attention: [{}]",
+            &config.host,
+            details.join("; ")
+        );
+    }
+    state.warning_sensors = names;
+}
+
+/// Forces every fan of a target to the failsafe speed
+fn enter_failsafe(
+    config: &TargetIlo,
+    failsafe: &FailsafeConfig,
+    state: &mut TargetState,
+    reason: &str,
+) -> Result<()> {
+    if !state.failsafe.is_active() {
+        error!(
+            "Entering failsafe for {}: {}, forcing fans to {}%",
+            &config.host, reason, failsafe.fan_speed
+        );
+    } else {
+        warn!("Failsafe of {} still active: {}", &config.host, reason);
+    }
+
+    let commands = crate::gen_ssh::generate_failsafe_commands(config, failsafe.fan_speed);
+    debug!("Failsafe commands for {}: {:?}", &config.host, &commands);
+    exec_commands(config, state, commands)?;
+    state.failsafe.enter();
+    state.applied.clear();
+    state.verification.clear();
+
+    Ok(())
+}
+
+/// Executes fan control commands over the SSH session of a target
+///
+/// The session is opened on first use and kept in the target's state, so
+/// later runs skip the slow handshake of the iLO. Dead sessions are
+/// reconnected transparently. Commands rejected by the iLO are reported as
+/// an error.
+fn exec_commands(
+    config: &TargetIlo,
+    state: &mut TargetState,
+    commands: Vec<String>,
+) -> Result<Vec<String>> {
+    let host = config.host.clone();
+    let client = state
+        .ssh
+        .as_mut()
+        .expect("SSH client is created with the target state");
+
+    let output = match client.exec_with_reconnect(commands.clone()) {
         Ok(output) => {
             debug!("Fan control output for {}: {:?}", &host, output);
+            output
         }
         Err(e) => {
             error!("Failed to execute commands on {}: {}", &host, e);
             return Err(e);
         }
+    };
+
+    // Fail loudly when the iLO did not apply the commands
+    if let Err(e) = crate::fan_output::check_fan_responses(&commands, &output) {
+        error!("Fan commands were rejected by {}: {}", &host, e);
+        return Err(e);
     }
 
-    Ok(())
+    Ok(output)
 }
@@ -0,0 +1,146 @@
+use anyhow::Result;
+use log::{debug, info, warn};
+
+use crate::config::TargetFans;
+use crate::fan_output::FanInfo;
+use crate::gen_ssh::FanSpeed;
+use crate::ssh::SshClient;
+
+/// Shows the fan settings in effect on the iLO
+///
+/// Runs `fan info` over SSH and prints the parsed table, or JSON when
+/// `json` is set.
+pub fn show_fan_info(
+    host: Option<String>,
+    user: Option<String>,
+    password: Option<String>,
+    json: bool,
+) -> Result<()> {
+    debug!("Showing fan info");
+
+    let mut client = connect(host, user, password)?;
+    let fan_info = read_fan_info(&mut client)?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&fan_info)?);
+    } else {
+        println!("{}", fan_info);
+    }
+
+    Ok(())
+}
+
+/// Sets the fan speed limits of the given fans once
+///
+/// # Arguments
+/// * `fans` - Fan numbers starting from 1
+/// * `max` - Fan speed ceiling percentage (0-100)
+/// * `min` - Optional fan speed floor percentage (0-100), the iLO's
+///   default floor when not set
+pub fn set_fan_speed(
+    host: Option<String>,
+    user: Option<String>,
+    password: Option<String>,
+    fans: Vec<u8>,
+    max: u8,
+    min: Option<u8>,
+) -> Result<()> {
+    debug!("Setting fan speed of fans {:?}", fans);
+
+    if min.is_some_and(|min| min > max) {
+        return Err(anyhow::anyhow!("--min must not exceed --max"));
+    }
+
+    let fan_speed = FanSpeed {
+        max: max as f32,
+        min: min.map(|min| min as f32),
+    };
+    let commands =
+        crate::gen_ssh::generate_manual_commands(&TargetFans::TargetFans(fans), fan_speed);
+
+    let mut client = connect(host, user, password)?;
+    exec_commands(&mut client, commands)
+}
+
+/// Returns every fan to the iLO's own fan control
+///
+/// Reads the fans from `fan info`, lifts their caps and restores the iLO's
+/// default floor.
+///
+/// # Arguments
+/// * `num_fans` - Number of fans to reset when `fan info` cannot be read
+pub fn reset_fans(
+    host: Option<String>,
+    user: Option<String>,
+    password: Option<String>,
+    num_fans: Option<u8>,
+) -> Result<()> {
+    debug!("Resetting fans");
+
+    let mut client = connect(host, user, password)?;
+    let fans = match (read_fan_info(&mut client), num_fans) {
+        (Ok(fan_info), _) if !fan_info.fans.is_empty() => {
+            TargetFans::TargetFans(fan_info.fans.iter().map(|fan| fan.id + 1).collect())
+        }
+        (result, Some(num_fans)) => {
+            let reason = result.map_or_else(|e| e.to_string(), |_| "no fans listed".to_string());
+            warn!(
+                "Cannot read the fans from `fan info` ({}), resetting {} fans",
+                reason, num_fans
+            );
+            TargetFans::NumFans(num_fans)
+        }
+        (Err(e), None) => {
+            return Err(e.context("Cannot read the fans from `fan info`, pass --num-fans"));
+        }
+        (Ok(_), None) => {
+            return Err(anyhow::anyhow!("`fan info` lists no fans, pass --num-fans"));
+        }
+    };
+
+    // The iLO's default floor applies when no floor is set
+    let fan_speed = FanSpeed {
+        max: 100.0,
+        min: None,
+    };
+    let commands = crate::gen_ssh::generate_manual_commands(&fans, fan_speed);
+
+    exec_commands(&mut client, commands)
+}
+
+/// Asks for missing connection details and connects to the iLO over SSH
+fn connect(
+    host: Option<String>,
+    user: Option<String>,
+    password: Option<String>,
+) -> Result<SshClient> {
+    let (host, user, password) = crate::cmds::utils::get_connection_info(host, user, password);
+
+    info!("Connecting to iLO4 at {}@{}", user, host);
+    let mut client = SshClient::new(host, user, password);
+    client.connect()?;
+
+    Ok(client)
+}
+
+/// Runs `fan info` and parses its table
+fn read_fan_info(client: &mut SshClient) -> Result<FanInfo> {
+    let output = client.exec(vec!["fan info".to_string()])?;
+    debug!("fan info output: {:?}", output);
+
+    FanInfo::parse(&output.concat())
+}
+
+/// Runs fan commands and checks that the iLO accepted them
+fn exec_commands(client: &mut SshClient, commands: Vec<String>) -> Result<()> {
+    debug!("Fan commands: {:?}", commands);
+
+    let output = client.exec(commands.clone())?;
+    crate::fan_output::check_fan_responses(&commands, &output)?;
+
+    for command in &commands {
+        info!("Applied `{}`", command);
+    }
+
+    Ok(())
+}
@@ -1,4 +1,7 @@
-use crate::config::{FanConfig, IloConfig, TargetFans, TargetIlo};
+use crate::config::{
+    ControlMode, FailsafeConfig, FanConfig, IloConfig, TargetFans, TargetIlo, TlsConfig,
+    VerificationConfig,
+};
 use log::{debug, error, info};
 
 pub fn show_sample(path: String, dual: bool) {
@@ -8,21 +11,25 @@ pub fn show_sample(path: String, dual: bool) {
             min_temp: 0,
             max_temp: 55,
             max_fan_speed: 20,
+            min_fan_speed: None,
         },
         FanConfig {
             min_temp: 55,
             max_temp: 60,
             max_fan_speed: 40,
+            min_fan_speed: None,
         },
         FanConfig {
             min_temp: 61,
             max_temp: 70,
             max_fan_speed: 70,
+            min_fan_speed: None,
         },
         FanConfig {
             min_temp: 71,
             max_temp: 100,
             max_fan_speed: 100,
+            min_fan_speed: None,
         },
     ];
     let target_ilo = TargetIlo {
@@ -30,10 +37,23 @@ pub fn show_sample(path: String, dual: bool) {
         user: String::from("USERNAME"),
         password_base64: String::from("PASSWORD_BASE64"),
         target_fans: target_fans.clone(),
+        sensors: vec![],
+        control_mode: ControlMode::Step,
+        hysteresis: 0,
         temperature_fan_config: fan_config.clone(),
+        pid: None,
+        curves: vec![],
+        fan_groups: vec![],
+        fan_failure_compensation: 20,
+        tls: TlsConfig::default(),
     };
     let mut ilo_config = IloConfig {
         run_period_seconds: 60,
+        shutdown_fan_speed: 100,
+        max_backoff_seconds: 300,
+        force_refresh_seconds: 600,
+        failsafe: FailsafeConfig::default(),
+        verification: VerificationConfig::default(),
         targets: vec![target_ilo],
     };
 
@@ -43,7 +63,15 @@ pub fn show_sample(path: String, dual: bool) {
             user: String::from("USERNAME"),
             password_base64: String::from("PASSWORD_BASE64"),
             target_fans,
+            sensors: vec![],
+            control_mode: ControlMode::Step,
+            hysteresis: 0,
             temperature_fan_config: fan_config,
+            pid: None,
+            curves: vec![],
+            fan_groups: vec![],
+            fan_failure_compensation: 20,
+            tls: TlsConfig::default(),
         };
         ilo_config.targets.push(target_ilo2);
     }
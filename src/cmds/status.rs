@@ -1,7 +1,71 @@
-use log::{debug, error, info};
+use std::time::Duration;
+
+use anyhow::Result;
+use clap::ValueEnum;
+use console::{style, Term};
+use log::{debug, error, info, warn};
 use tokio::runtime::Runtime;
+use tokio::task::JoinSet;
+
+use crate::config::{IloConfig, TlsConfig, TlsMode};
+use crate::cputemp::TempData;
+use crate::redfish::RedfishClient;
+use crate::status::{status_csv, status_table, SeenReadings, TargetStatus};
+
+/// Output format of the status command
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum OutputFormat {
+    /// Human readable text on stdout
+    #[default]
+    Text,
+    /// JSON on stdout
+    Json,
+    /// YAML on stdout
+    Yaml,
+    /// CSV with one row per CPU, sensor and fan on stdout
+    Csv,
+}
 
-pub fn show_status(host: Option<String>, user: Option<String>, password: Option<String>) {
+/// Builds the TLS settings of the `status` command line options
+///
+/// # Arguments
+/// * `ca_bundle` - PEM file with CA certificates to trust
+/// * `fingerprint` - SHA-256 fingerprint of the iLO's certificate
+/// * `pin_file` - File the certificate is pinned in on first use
+/// * `insecure` - Accept any certificate
+///
+/// # Returns
+/// * `TlsConfig` - `Insecure` or `Pin` when requested, `Verify` otherwise
+pub fn tls_config(
+    ca_bundle: Option<String>,
+    fingerprint: Option<String>,
+    pin_file: Option<String>,
+    insecure: bool,
+) -> TlsConfig {
+    let mode = if insecure {
+        TlsMode::Insecure
+    } else if fingerprint.is_some() || pin_file.is_some() {
+        TlsMode::Pin
+    } else {
+        TlsMode::Verify
+    };
+
+    TlsConfig {
+        mode,
+        ca_bundle,
+        fingerprint_sha256: fingerprint,
+        pin_file,
+    }
+}
+
+pub fn show_status(
+    host: Option<String>,
+    user: Option<String>,
+    password: Option<String>,
+    tls: TlsConfig,
+    format: OutputFormat,
+    watch: Option<u64>,
+) {
     debug!("Showing status");
 
     let (host, user, password) = crate::cmds::utils::get_connection_info(host, user, password);
@@ -13,7 +77,20 @@ pub fn show_status(host: Option<String>, user: Option<String>, password: Option<
     match rt {
         Ok(runtime) => {
             debug!("Runtime created successfully");
-            runtime.block_on(show_status_runner(host, user, password));
+            let result = match watch {
+                Some(interval) => runtime.block_on(watch_status_runner(
+                    host,
+                    user,
+                    password,
+                    tls,
+                    Duration::from_secs(interval),
+                )),
+                None => runtime.block_on(show_status_runner(host, user, password, tls, format)),
+            };
+            if let Err(e) = result {
+                error!("Failed to get temperature data: {}", e);
+                std::process::exit(1);
+            }
         }
         Err(e) => {
             error!("Failed to create runtime: {}", e);
@@ -22,15 +99,213 @@ pub fn show_status(host: Option<String>, user: Option<String>, password: Option<
     }
 }
 
-async fn show_status_runner(host: String, user: String, password: String) {
+async fn show_status_runner(
+    host: String,
+    user: String,
+    password: String,
+    tls: TlsConfig,
+    format: OutputFormat,
+) -> Result<()> {
     info!("Connecting to iLO4 at {}@{}", user, host);
 
-    match crate::cputemp::get_temp_data(host.as_str(), user.as_str(), password.as_str()).await {
-        Ok(temp_data) => {
-            info!("Temperature data:\n {}", temp_data);
+    let client = RedfishClient::new(&host, &user, &password, &tls)?;
+    let temp_data = read_temp_data_once(client).await?;
+    print_temp_data(&temp_data, format)
+}
+
+/// Reads the temperatures once and deletes the Redfish session again
+async fn read_temp_data_once(mut client: RedfishClient) -> Result<TempData> {
+    let result = crate::cputemp::get_temp_data(&mut client).await;
+
+    if let Err(e) = client.logout().await {
+        warn!("{}", e);
+    }
+
+    result
+}
+
+/// Prints temperature data in the selected format
+///
+/// Every format goes to stdout, so the output is shown regardless of the log
+/// level and can be piped into other programs.
+fn print_temp_data(temp_data: &TempData, format: OutputFormat) -> Result<()> {
+    match format {
+        OutputFormat::Text => println!("{}", temp_data),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(temp_data)?),
+        OutputFormat::Yaml => print!("{}", serde_yaml_ng::to_string(temp_data)?),
+        OutputFormat::Csv => print!("{}", temp_data.to_csv()?),
+    }
+
+    Ok(())
+}
+
+/// Shows the status of every target of a configuration file
+///
+/// All targets are queried at the same time. Besides the readings, the
+/// temperature band each fan group is in and the fan speed the daemon would
+/// apply are shown.
+pub fn show_config_status(config_path: String, format: OutputFormat) {
+    debug!("Showing status of the targets in {}", config_path);
+
+    let result = IloConfig::from_toml_file(&config_path).and_then(|config| {
+        let runtime = Runtime::new()?;
+        runtime.block_on(show_config_status_runner(config, format))
+    });
+
+    if let Err(e) = result {
+        error!("Failed to show status: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn show_config_status_runner(config: IloConfig, format: OutputFormat) -> Result<()> {
+    info!("Querying {} targets", config.targets.len());
+
+    let mut tasks = JoinSet::new();
+    for (index, target) in config.targets.iter().cloned().enumerate() {
+        tasks.spawn(async move {
+            let result = match RedfishClient::from_target(&target) {
+                Ok(client) => read_temp_data_once(client).await,
+                Err(e) => Err(e),
+            };
+            let status = match result {
+                Ok(temp_data) => TargetStatus::new(&target, &temp_data),
+                Err(e) => TargetStatus::failed(&target, e),
+            };
+            (index, status)
+        });
+    }
+
+    let mut statuses: Vec<(usize, TargetStatus)> = Vec::new();
+    while let Some(result) = tasks.join_next().await {
+        statuses.push(result?);
+    }
+    statuses.sort_by_key(|(index, _)| *index);
+    let statuses: Vec<TargetStatus> = statuses.into_iter().map(|(_, status)| status).collect();
+
+    match format {
+        OutputFormat::Text => print!("{}", status_table(&statuses)),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&statuses)?),
+        OutputFormat::Yaml => print!("{}", serde_yaml_ng::to_string(&statuses)?),
+        OutputFormat::Csv => print!("{}", status_csv(&statuses)?),
+    }
+
+    Ok(())
+}
+
+/// Polls the iLO and redraws the readings in place until interrupted
+///
+/// Readings that changed since the previous sample are highlighted, and the
+/// lowest and highest value of every reading during the session are shown.
+/// All samples are read over one Redfish session, which is deleted on Ctrl-C.
+async fn watch_status_runner(
+    host: String,
+    user: String,
+    password: String,
+    tls: TlsConfig,
+    interval: Duration,
+) -> Result<()> {
+    info!("Watching iLO4 at {}@{}", user, host);
+
+    let term = Term::stdout();
+    let mut seen = SeenReadings::default();
+    let mut sample = 0;
+    let mut client = RedfishClient::new(&host, &user, &password, &tls)?;
+
+    loop {
+        let result = crate::cputemp::get_temp_data(&mut client).await;
+        sample += 1;
+
+        term.clear_screen()?;
+        term.write_line(&format!(
+            "{}@{} - sample {}, every {} seconds (Ctrl-C to stop)\n",
+            user,
+            host,
+            sample,
+            interval.as_secs()
+        ))?;
+
+        match result {
+            Ok(temp_data) => draw_temp_data(&term, &temp_data, &mut seen)?,
+            Err(e) => term.write_line(&format!(
+                "{}",
+                style(format!("Failed to get temperature data: {}", e)).red()
+            ))?,
         }
-        Err(e) => {
-            error!("Failed to get temperature data: {}", e);
+
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = tokio::signal::ctrl_c() => break,
         }
     }
+
+    // Do not leave the session behind on the iLO
+    client.logout().await
+}
+
+/// Draws one sample of the watch mode and updates the seen ranges
+fn draw_temp_data(term: &Term, temp_data: &TempData, seen: &mut SeenReadings) -> Result<()> {
+    if temp_data.high_temp_critical_reached_component {
+        term.write_line(&format!(
+            "{} {}\n",
+            style("System Temperature Status: CRITICAL").red().bold(),
+            temp_data.high_temp_component_name.join(", ")
+        ))?;
+    } else {
+        term.write_line("System Temperature Status: Normal\n")?;
+    }
+
+    term.write_line(&format!(
+        "{:<32} {:>7} {:>8} {:>5} {:>5}  Status",
+        "Reading", "Current", "Change", "Min", "Max"
+    ))?;
+
+    let temperatures = temp_data.sensors.iter().map(|sensor| {
+        (
+            sensor.name.clone(),
+            sensor.current,
+            "°C",
+            Some(sensor.health.as_str()),
+        )
+    });
+    let fans = temp_data.fans.iter().map(|fan| {
+        (
+            fan.name.clone(),
+            Some(fan.current),
+            "%",
+            Some(fan.status.as_str()),
+        )
+    });
+
+    for (name, current, unit, status) in temperatures.chain(fans) {
+        let status = match status {
+            Some("OK" | "Absent") | None => style(status.unwrap_or("").to_string()),
+            Some(status) => style(status.to_string()).red().bold(),
+        };
+
+        // Absent and disabled sensors have no reading
+        let Some(current) = current else {
+            term.write_line(&format!(
+                "{:<32} {:>7} {:>8} {:>5} {:>5}  {}",
+                name, "-", "", "-", "-", status
+            ))?;
+            continue;
+        };
+        let (change, reading) = seen.update(&name, current);
+
+        let value = format!("{:>5}{:<2}", current, unit);
+        let change_text = crate::status::change_text(change);
+        let (value, change) = match change {
+            0 => (style(value), style(change_text)),
+            change if change > 0 => (style(value).bold(), style(change_text).red()),
+            _ => (style(value).bold(), style(change_text).green()),
+        };
+
+        term.write_line(&format!(
+            "{:<32} {} {:>8} {:>5} {:>5}  {}",
+            name, value, change, reading.min, reading.max, status
+        ))?;
+    }
+
+    Ok(())
 }
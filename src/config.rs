@@ -1,9 +1,9 @@
-use anyhow::Ok;
 use anyhow::Result;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::Path;
-use validator::{Validate, ValidationErrors};
+use validator::{Validate, ValidationError, ValidationErrors};
 
 /// Configuration for ILO fan control
 ///
@@ -13,16 +13,140 @@ use validator::{Validate, ValidationErrors};
 pub struct IloConfig {
     /// The period in seconds between fan control updates
     pub run_period_seconds: u8,
+    /// Fan speed percentage (0-100) applied to every fan when the daemon stops
+    #[serde(default = "default_shutdown_fan_speed")]
+    #[validate(range(min = 0, max = 100))]
+    pub shutdown_fan_speed: u8,
+    /// Upper limit in seconds for the retry delay of a failing target
+    #[serde(default = "default_max_backoff_seconds")]
+    pub max_backoff_seconds: u32,
+    /// Seconds after which unchanged fan commands are sent again
+    /// (0 sends them on every run)
+    #[serde(default = "default_force_refresh_seconds")]
+    pub force_refresh_seconds: u32,
+    /// When every fan is forced to an emergency speed
+    #[serde(default)]
+    #[validate(nested)]
+    pub failsafe: FailsafeConfig,
+    /// How applied fan speeds are checked against the fan readings
+    #[serde(default)]
+    #[validate(nested)]
+    pub verification: VerificationConfig,
     /// List of ILO targets to control
     #[validate(nested)]
     pub targets: Vec<TargetIlo>,
 }
 
+/// Default fan speed on shutdown, which lifts every cap set by the daemon
+fn default_shutdown_fan_speed() -> u8 {
+    100
+}
+
+/// Default upper limit of the retry delay of a failing target
+fn default_max_backoff_seconds() -> u32 {
+    300
+}
+
+/// Default interval for resending unchanged fan commands
+fn default_force_refresh_seconds() -> u32 {
+    600
+}
+
+/// Failsafe policy of the daemon
+///
+/// Forces every fan of a target to `fan_speed` when a component reaches its
+/// critical temperature or when the temperatures of the target can no longer
+/// be trusted.
+#[derive(Serialize, Deserialize, Debug, Validate, Clone, PartialEq)]
+pub struct FailsafeConfig {
+    /// Emergency fan speed percentage (0-100)
+    #[serde(default = "default_failsafe_fan_speed")]
+    #[validate(range(min = 0, max = 100))]
+    pub fan_speed: u8,
+    /// Enter the failsafe when the iLO reports a component above its
+    /// critical threshold
+    #[serde(default = "default_failsafe_on_critical")]
+    pub on_critical: bool,
+    /// Number of failed temperature reads in a row before entering the
+    /// failsafe (0 disables the check)
+    #[serde(default = "default_failsafe_max_read_failures")]
+    pub max_read_failures: u32,
+    /// Seconds since the last successful temperature read after which the
+    /// readings are considered stale (0 disables the check)
+    #[serde(default = "default_failsafe_stale_after_seconds")]
+    pub stale_after_seconds: u32,
+}
+
+impl Default for FailsafeConfig {
+    fn default() -> Self {
+        FailsafeConfig {
+            fan_speed: default_failsafe_fan_speed(),
+            on_critical: default_failsafe_on_critical(),
+            max_read_failures: default_failsafe_max_read_failures(),
+            stale_after_seconds: default_failsafe_stale_after_seconds(),
+        }
+    }
+}
+
+fn default_failsafe_fan_speed() -> u8 {
+    100
+}
+
+fn default_failsafe_on_critical() -> bool {
+    true
+}
+
+fn default_failsafe_max_read_failures() -> u32 {
+    3
+}
+
+fn default_failsafe_stale_after_seconds() -> u32 {
+    600
+}
+
+/// Verification of applied fan speeds
+///
+/// After fan commands were sent, the fan readings of the next run are
+/// checked against the new caps. Disabled by default.
+#[derive(Serialize, Deserialize, Debug, Validate, Clone, PartialEq)]
+pub struct VerificationConfig {
+    /// Check that the fans follow the applied fan speeds
+    #[serde(default)]
+    pub enabled: bool,
+    /// Allowed deviation from the applied fan speed in percentage points
+    #[serde(default = "default_verification_tolerance")]
+    #[validate(range(min = 0, max = 100))]
+    pub tolerance: u8,
+    /// Number of times the commands are sent again before the target is
+    /// reported as failed
+    #[serde(default = "default_verification_retries")]
+    pub retries: u8,
+}
+
+impl Default for VerificationConfig {
+    fn default() -> Self {
+        VerificationConfig {
+            enabled: false,
+            tolerance: default_verification_tolerance(),
+            retries: default_verification_retries(),
+        }
+    }
+}
+
+fn default_verification_tolerance() -> u8 {
+    5
+}
+
+fn default_verification_retries() -> u8 {
+    1
+}
+
 /// Configuration for a single ILO target
 ///
 /// This structure contains connection details and fan control settings
 /// for a specific ILO interface.
 #[derive(Serialize, Deserialize, Debug, Validate, Clone)]
+#[validate(schema(function = "validate_target_ilo"))]
 pub struct TargetIlo {
     /// The hostname or IP address of the ILO interface
     pub host: String,
@@ -31,16 +155,294 @@ pub struct TargetIlo {
     /// Base64 encoded password for ILO authentication
     pub password_base64: String,
     /// Fan control target configuration
+    #[serde(default)]
+    #[validate(custom(function = "validate_target_fans"))]
     pub target_fans: TargetFans,
+    /// Sensors whose highest temperature drives the fan speed
+    /// (the CPU sensors when empty)
+    #[serde(default)]
+    pub sensors: Vec<SensorSelector>,
+    /// How `temperature_fan_config` is turned into a fan speed
+    #[serde(default)]
+    pub control_mode: ControlMode,
+    /// Degrees Celsius the temperature must fall below the current band's
+    /// `min_temp` before a lower fan speed is applied (0 disables hysteresis)
+    #[serde(default)]
+    pub hysteresis: u8,
     /// Temperature-based fan speed configuration
+    #[serde(default)]
     #[validate(nested)]
     pub temperature_fan_config: Vec<FanConfig>,
+    /// PID controller settings, required when `control_mode` is `Pid`
+    #[serde(default)]
+    #[validate(nested)]
+    pub pid: Option<PidConfig>,
+    /// Additional fan curves driven by their own sensors
+    #[serde(default)]
+    #[validate(nested)]
+    pub curves: Vec<FanCurve>,
+    /// Additional groups of fans with their own fan curves
+    #[serde(default)]
+    #[validate(nested)]
+    pub fan_groups: Vec<FanGroup>,
+    /// Percentage points added to the fan speeds while a fan of the target
+    /// has failed (0 disables the compensation)
+    #[serde(default = "default_fan_failure_compensation")]
+    #[validate(range(min = 0, max = 100))]
+    pub fan_failure_compensation: u8,
+    /// Verification of the iLO's HTTPS certificate
+    #[serde(default)]
+    #[validate(nested)]
+    pub tls: TlsConfig,
+}
+
+/// Default compensation for a failed fan
+fn default_fan_failure_compensation() -> u8 {
+    20
+}
+
+impl TargetIlo {
+    /// Returns the fan curve described by the top-level fields of the target
+    pub fn primary_curve(&self) -> FanCurve {
+        FanCurve {
+            sensors: self.sensors.clone(),
+            control_mode: self.control_mode,
+            hysteresis: self.hysteresis,
+            temperature_fan_config: self.temperature_fan_config.clone(),
+            pid: self.pid.clone(),
+        }
+    }
+
+    /// Returns every fan group of the target
+    ///
+    /// The first group consists of the top-level `target_fans`, the primary
+    /// curve and `curves`, followed by the entries of `fan_groups`.
+    pub fn fan_groups(&self) -> Vec<FanGroup> {
+        let mut groups = vec![FanGroup {
+            target_fans: self.target_fans.clone(),
+            curve: self.primary_curve(),
+            curves: self.curves.clone(),
+        }];
+        groups.extend(self.fan_groups.iter().cloned());
+        groups
+    }
+}
+
+/// Verification of the iLO's HTTPS certificate
+#[derive(Serialize, Deserialize, Debug, Validate, Clone, Default, PartialEq)]
+#[validate(schema(function = "validate_tls_config"))]
+pub struct TlsConfig {
+    /// How the certificate is checked
+    #[serde(default)]
+    pub mode: TlsMode,
+    /// PEM file with CA certificates trusted in addition to the system ones
+    /// (`Verify` only)
+    #[serde(default)]
+    pub ca_bundle: Option<String>,
+    /// SHA-256 fingerprint of the certificate, in hex with or without colons
+    /// (`Pin` only)
+    #[serde(default)]
+    pub fingerprint_sha256: Option<String>,
+    /// File the certificate seen on first use is pinned in, for `Pin`
+    /// without `fingerprint_sha256`
+    #[serde(default)]
+    pub pin_file: Option<String>,
+}
+
+/// Certificate verification mode
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub enum TlsMode {
+    /// Verify the certificate chain and host name against the system CAs
+    /// and `ca_bundle`
+    #[default]
+    Verify,
+    /// Accept only the certificate with the SHA-256 fingerprint
+    /// `fingerprint_sha256`, or the one pinned in `pin_file` on first use
+    Pin,
+    /// Accept any certificate, e.g. the self-signed default certificate of
+    /// the iLO
+    Insecure,
+}
+
+/// Checks that the TLS settings fit the selected mode
+fn validate_tls_config(tls: &TlsConfig) -> Result<(), ValidationError> {
+    if tls.ca_bundle.is_some() && tls.mode != TlsMode::Verify {
+        return Err(ValidationError::new("tls_ca_bundle")
+            .with_message("ca_bundle is only used with mode Verify".into()));
+    }
+    if tls.pin_file.is_some() && tls.mode != TlsMode::Pin {
+        return Err(ValidationError::new("tls_pin_file")
+            .with_message("pin_file is only used with mode Pin".into()));
+    }
+    if tls.pin_file.is_some() && tls.fingerprint_sha256.is_some() {
+        return Err(ValidationError::new("tls_pin_file")
+            .with_message("pin_file cannot be combined with fingerprint_sha256".into()));
+    }
+    match &tls.fingerprint_sha256 {
+        Some(_) if tls.mode != TlsMode::Pin => Err(ValidationError::new("tls_fingerprint")
+            .with_message("fingerprint_sha256 is only used with mode Pin".into())),
+        Some(fingerprint) => match crate::redfish::parse_fingerprint(fingerprint) {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                Err(ValidationError::new("tls_fingerprint").with_message(e.to_string().into()))
+            }
+        },
+        // A pin kept only in memory would be lost on restart
+        None if tls.mode == TlsMode::Pin && tls.pin_file.is_none() => {
+            Err(ValidationError::new("tls_fingerprint")
+                .with_message("fingerprint_sha256 or pin_file is required with mode Pin".into()))
+        }
+        None => Ok(()),
+    }
+}
+
+/// Group of fans controlled by their own fan curves
+///
+/// The curve settings are written directly in the group, additional curves
+/// can be added with `curves`. A fan that belongs to several groups runs at
+/// the highest speed demanded by any of them.
+#[derive(Serialize, Deserialize, Debug, Validate, Clone, PartialEq)]
+pub struct FanGroup {
+    /// Fans belonging to this group
+    #[validate(custom(function = "validate_target_fans"))]
+    pub target_fans: TargetFans,
+    /// Primary fan curve of the group
+    #[serde(flatten)]
+    #[validate(nested)]
+    pub curve: FanCurve,
+    /// Additional fan curves driven by their own sensors
+    #[serde(default)]
+    #[validate(nested)]
+    pub curves: Vec<FanCurve>,
+}
+
+impl FanGroup {
+    /// Returns every fan curve of the group, starting with the primary curve
+    pub fn fan_curves(&self) -> Vec<FanCurve> {
+        let mut curves = vec![self.curve.clone()];
+        curves.extend(self.curves.iter().cloned());
+        curves
+    }
+}
+
+/// Checks settings of a target that depend on each other
+fn validate_target_ilo(target: &TargetIlo) -> Result<(), ValidationError> {
+    validate_fan_curve(&target.primary_curve())
+}
+
+/// Fan curve driven by its own set of sensors
+///
+/// Each curve demands a fan speed from the highest temperature of its
+/// sensors. When a target has several curves, the highest demanded fan
+/// speed is applied.
+#[derive(Serialize, Deserialize, Debug, Validate, Clone, PartialEq)]
+#[validate(schema(function = "validate_fan_curve"))]
+pub struct FanCurve {
+    /// Sensors whose highest temperature drives this curve
+    /// (the CPU sensors when empty)
+    #[serde(default)]
+    pub sensors: Vec<SensorSelector>,
+    /// How `temperature_fan_config` is turned into a fan speed
+    #[serde(default)]
+    pub control_mode: ControlMode,
+    /// Degrees Celsius the temperature must fall below the current band's
+    /// `min_temp` before a lower fan speed is applied (0 disables hysteresis)
+    #[serde(default)]
+    pub hysteresis: u8,
+    /// Temperature-based fan speed configuration
+    #[serde(default)]
+    #[validate(nested)]
+    pub temperature_fan_config: Vec<FanConfig>,
+    /// PID controller settings, required when `control_mode` is `Pid`
+    #[serde(default)]
+    #[validate(nested)]
+    pub pid: Option<PidConfig>,
+}
+
+/// Checks settings of a fan curve that depend on each other
+fn validate_fan_curve(curve: &FanCurve) -> Result<(), ValidationError> {
+    if curve.control_mode == ControlMode::Pid && curve.pid.is_none() {
+        return Err(ValidationError::new("pid_config_missing")
+            .with_message("control_mode Pid requires a [pid] section".into()));
+    }
+    Ok(())
+}
+
+/// Temperature sensor selection
+///
+/// Selects sensors of the iLO Thermal resource that feed the fan control.
+/// When several selectors are given, the highest temperature of all matching
+/// sensors is used.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum SensorSelector {
+    /// Sensor name, with or without the numeric prefix (e.g. `PCI 1 Zone`)
+    Name(String),
+    /// Regular expression matched against the sensor name
+    Regex(SensorRegex),
+    /// PhysicalContext of the sensor (e.g. `CPU`, `Intake`, `SystemBoard`)
+    PhysicalContext(String),
+}
+
+/// Regular expression of a `SensorSelector`
+///
+/// The pattern is compiled when the configuration is loaded, so an invalid
+/// pattern rejects the configuration and matching does not recompile it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(try_from = "String", into = "String")]
+pub struct SensorRegex(Regex);
+
+impl SensorRegex {
+    /// Compiles a sensor name pattern
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - Regular expression matched against the sensor name
+    ///
+    /// # Returns
+    ///
+    /// * `Result<SensorRegex>` - Compiled pattern or an error for an invalid pattern
+    pub fn new(pattern: &str) -> Result<Self> {
+        Regex::new(pattern)
+            .map(SensorRegex)
+            .map_err(|e| anyhow::anyhow!("Invalid sensor regex '{}': {}", pattern, e))
+    }
+
+    /// Checks whether the pattern matches a sensor name
+    pub fn is_match(&self, name: &str) -> bool {
+        self.0.is_match(name)
+    }
+
+    /// Returns the pattern as written in the configuration
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl PartialEq for SensorRegex {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl TryFrom<String> for SensorRegex {
+    type Error = String;
+
+    fn try_from(pattern: String) -> Result<Self, Self::Error> {
+        SensorRegex::new(&pattern).map_err(|e| e.to_string())
+    }
+}
+
+impl From<SensorRegex> for String {
+    fn from(regex: SensorRegex) -> Self {
+        regex.as_str().to_string()
+    }
 }
 
 /// Configuration for temperature-based fan control
 ///
 /// Defines the fan speed settings for specific temperature ranges.
-#[derive(Serialize, Deserialize, Debug, Validate, Clone)]
+#[derive(Serialize, Deserialize, Debug, Validate, Clone, PartialEq)]
+#[validate(schema(function = "validate_fan_config"))]
 pub struct FanConfig {
     /// Minimum temperature threshold in Celsius
     pub min_temp: u8,
@@ -49,12 +451,77 @@ pub struct FanConfig {
     /// Maximum fan speed percentage (0-100)
     #[validate(range(min = 0, max = 100))]
     pub max_fan_speed: u8,
+    /// Minimum fan speed percentage (0-100), not set by default
+    #[serde(default)]
+    #[validate(range(min = 0, max = 100))]
+    pub min_fan_speed: Option<u8>,
+}
+
+/// Checks that the fan speed floor of a band does not exceed its ceiling
+fn validate_fan_config(fan_config: &FanConfig) -> Result<(), ValidationError> {
+    if let Some(min_fan_speed) = fan_config.min_fan_speed {
+        if min_fan_speed > fan_config.max_fan_speed {
+            return Err(ValidationError::new("fan_speed_range")
+                .with_message("min_fan_speed must not exceed max_fan_speed".into()));
+        }
+    }
+    Ok(())
+}
+
+/// Fan speed calculation mode
+///
+/// Selects how the entries of `temperature_fan_config` are interpreted.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub enum ControlMode {
+    /// Use the flat `max_fan_speed` of the band containing the temperature
+    #[default]
+    Step,
+    /// Treat each band as a `(min_temp, max_fan_speed)` point and
+    /// interpolate linearly between neighbouring points
+    Linear,
+    /// Closed-loop PID control towards the target temperature in `pid`
+    Pid,
+}
+
+/// Configuration for the PID controller
+///
+/// The controller drives the fan speed so that the temperature settles at
+/// `target_temp`. The output is clamped to `min_fan_speed..=max_fan_speed`.
+#[derive(Serialize, Deserialize, Debug, Validate, Clone, PartialEq)]
+#[validate(schema(function = "validate_pid_config"))]
+pub struct PidConfig {
+    /// Target temperature in Celsius
+    pub target_temp: u8,
+    /// Proportional gain (% fan speed per °C of error)
+    #[validate(range(min = 0.0))]
+    pub kp: f32,
+    /// Integral gain (% fan speed per °C·s of accumulated error)
+    #[validate(range(min = 0.0))]
+    pub ki: f32,
+    /// Derivative gain (% fan speed per °C/s of error change)
+    #[validate(range(min = 0.0))]
+    pub kd: f32,
+    /// Minimum fan speed percentage (0-100)
+    #[validate(range(min = 0, max = 100))]
+    pub min_fan_speed: u8,
+    /// Maximum fan speed percentage (0-100)
+    #[validate(range(min = 0, max = 100))]
+    pub max_fan_speed: u8,
+}
+
+/// Checks that the output range of the PID controller is not empty
+fn validate_pid_config(pid: &PidConfig) -> Result<(), ValidationError> {
+    if pid.min_fan_speed > pid.max_fan_speed {
+        return Err(ValidationError::new("pid_speed_range")
+            .with_message("min_fan_speed must not exceed max_fan_speed".into()));
+    }
+    Ok(())
 }
 
 /// Fan target specification
 ///
 /// Specifies either the number of fans to control or specific fan indices.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum TargetFans {
     /// Control a specific number of fans (starting from index 0)
     NumFans(u8),
@@ -62,6 +529,24 @@ pub enum TargetFans {
     TargetFans(Vec<u8>),
 }
 
+/// Checks that listed fans start from 1, as `fan p N` addresses them from 0
+fn validate_target_fans(target_fans: &TargetFans) -> Result<(), ValidationError> {
+    match target_fans {
+        TargetFans::TargetFans(fans) if fans.contains(&0) => {
+            Err(ValidationError::new("target_fans")
+                .with_message("TargetFans lists fans starting from 1".into()))
+        }
+        TargetFans::NumFans(_) | TargetFans::TargetFans(_) => Ok(()),
+    }
+}
+
+impl Default for TargetFans {
+    /// No fans, for targets that only use `fan_groups`
+    fn default() -> Self {
+        TargetFans::TargetFans(Vec::new())
+    }
+}
+
 impl IloConfig {
     /// Validates the configuration according to the defined validation rules
     ///
@@ -136,42 +621,70 @@ mod tests {
         let password_base64_456 = STANDARD.encode("password456");
         IloConfig {
             run_period_seconds: 60,
+            shutdown_fan_speed: 100,
+            max_backoff_seconds: 300,
+            force_refresh_seconds: 600,
+            failsafe: FailsafeConfig::default(),
+            verification: VerificationConfig::default(),
             targets: vec![
                 TargetIlo {
                     host: "192.168.1.100".to_string(),
                     user: "admin".to_string(),
                     password_base64,
                     target_fans: TargetFans::NumFans(3),
+                    sensors: vec![],
+                    control_mode: ControlMode::Step,
+                    hysteresis: 0,
                     temperature_fan_config: vec![
                         FanConfig {
                             min_temp: 30,
                             max_temp: 50,
                             max_fan_speed: 50,
+                            min_fan_speed: None,
                         },
                         FanConfig {
                             min_temp: 51,
                             max_temp: 70,
                             max_fan_speed: 100,
+                            min_fan_speed: Some(40),
                         },
                     ],
+                    pid: None,
+                    curves: vec![],
+                    fan_groups: vec![],
+                    fan_failure_compensation: 20,
+                    tls: TlsConfig::default(),
                 },
                 TargetIlo {
                     host: "192.168.1.101".to_string(),
                     user: "admin".to_string(),
                     password_base64: password_base64_456,
                     target_fans: TargetFans::TargetFans(vec![1, 2]),
+                    sensors: vec![
+                        SensorSelector::PhysicalContext("CPU".to_string()),
+                        SensorSelector::Regex(SensorRegex::new("PCI \\d Zone").unwrap()),
+                    ],
+                    control_mode: ControlMode::Linear,
+                    hysteresis: 3,
                     temperature_fan_config: vec![
                         FanConfig {
                             min_temp: 25,
                             max_temp: 40,
                             max_fan_speed: 30,
+                            min_fan_speed: None,
                         },
                         FanConfig {
                             min_temp: 41,
                             max_temp: 60,
                             max_fan_speed: 80,
+                            min_fan_speed: None,
                         },
                     ],
+                    pid: None,
+                    curves: vec![],
+                    fan_groups: vec![],
+                    fan_failure_compensation: 20,
+                    tls: TlsConfig::default(),
                 },
             ],
         }
@@ -198,6 +711,134 @@ mod tests {
         assert!(config.validate().is_err());
     }
 
+    #[test]
+    fn test_min_fan_speed_validation() {
+        let mut config = create_valid_config();
+        config.targets[0].temperature_fan_config[0].min_fan_speed = Some(30);
+        assert!(config.validate().is_ok());
+
+        // The floor must not exceed the ceiling of the band
+        config.targets[0].temperature_fan_config[0].min_fan_speed = Some(60);
+        assert!(config.validate().is_err());
+
+        config.targets[0].temperature_fan_config[1].min_fan_speed = Some(120);
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_invalid_shutdown_fan_speed_validation() {
+        let mut config = create_valid_config();
+        config.shutdown_fan_speed = 101;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_failsafe_config() -> Result<()> {
+        let mut temp_file = NamedTempFile::new()?;
+        write!(
+            temp_file,
+            r#"
+run_period_seconds = 60
+
+[failsafe]
+fan_speed = 80
+max_read_failures = 0
+
+[[targets]]
+host = "192.168.1.100"
+user = "admin"
+password_base64 = "cGFzc3dvcmQ="
+"#
+        )?;
+
+        let config = IloConfig::from_toml_file(temp_file.path())?;
+        assert_eq!(config.failsafe.fan_speed, 80);
+        assert!(config.failsafe.on_critical);
+        assert_eq!(config.failsafe.max_read_failures, 0);
+        assert_eq!(config.failsafe.stale_after_seconds, 600);
+
+        let mut config = create_valid_config();
+        config.failsafe.fan_speed = 101;
+        assert!(config.validate().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tls_config() -> Result<()> {
+        let mut temp_file = NamedTempFile::new()?;
+        write!(
+            temp_file,
+            r#"
+run_period_seconds = 60
+
+[[targets]]
+host = "192.168.1.100"
+user = "admin"
+password_base64 = "cGFzc3dvcmQ="
+
+[targets.tls]
+mode = "Pin"
+fingerprint_sha256 = "3aaeb67ae6f9b1e543d7fd286d39d61d84c4fb66a12a5186813174 35cd30fa2b"
+
+[[targets]]
+host = "192.168.1.101"
+user = "admin"
+password_base64 = "cGFzc3dvcmQ="
+"#
+        )?;
+
+        // The fingerprint contains a space
+        assert!(IloConfig::from_toml_file(temp_file.path()).is_err());
+
+        let mut config = create_valid_config();
+        assert_eq!(config.targets[0].tls.mode, TlsMode::Verify);
+
+        config.targets[0].tls = TlsConfig {
+            mode: TlsMode::Pin,
+            ca_bundle: None,
+            fingerprint_sha256: Some(
+                "3A:AE:B6:7A:E6:F9:B1:E5:43:D7:FD:28:6D:39:D6:1D:84:C4:FB:66:A1:2A:51:86:81:31:74:35:CD:30:FA:2B"
+                    .to_string(),
+            ),
+            pin_file: None,
+        };
+        assert!(config.validate().is_ok());
+
+        // Fingerprints only apply to the Pin mode
+        config.targets[0].tls.mode = TlsMode::Verify;
+        assert!(config.validate().is_err());
+
+        // The Pin mode needs a fingerprint or a file keeping the pin of the first use
+        config.targets[0].tls = TlsConfig {
+            mode: TlsMode::Pin,
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+
+        config.targets[0].tls.pin_file = Some("/var/lib/ilo4-fan-control/pins".to_string());
+        assert!(config.validate().is_ok());
+
+        // Either the fingerprint or the pin file
+        config.targets[0].tls.fingerprint_sha256 = Some("00".repeat(32));
+        assert!(config.validate().is_err());
+
+        // Pin files only apply to the Pin mode
+        config.targets[0].tls.fingerprint_sha256 = None;
+        config.targets[0].tls.mode = TlsMode::Verify;
+        assert!(config.validate().is_err());
+
+        // CA bundles only apply to the Verify mode
+        config.targets[0].tls = TlsConfig {
+            mode: TlsMode::Insecure,
+            ca_bundle: Some("/etc/ilo4-fan-control/ca.pem".to_string()),
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn test_load_from_toml_file() -> Result<()> {
         let config = create_valid_config();
@@ -217,6 +858,11 @@ mod tests {
         assert_eq!(first_target_fan_config.min_temp, 30);
         assert_eq!(first_target_fan_config.max_temp, 50);
         assert_eq!(first_target_fan_config.max_fan_speed, 50);
+        assert_eq!(first_target_fan_config.min_fan_speed, None);
+        assert_eq!(
+            loaded_config.targets[0].temperature_fan_config[1].min_fan_speed,
+            Some(40)
+        );
 
         Ok(())
     }
@@ -260,6 +906,287 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_target_fans_start_from_one() {
+        // ファン番号 0 は存在しないので拒否する
+        let mut config = create_valid_config();
+        config.targets[1].target_fans = TargetFans::TargetFans(vec![0, 1]);
+        assert!(config.validate().is_err());
+
+        // ファングループも同様に検証する
+        let mut config = create_valid_config();
+        config.targets[0].fan_groups = vec![FanGroup {
+            target_fans: TargetFans::TargetFans(vec![0]),
+            curve: config.targets[0].primary_curve(),
+            curves: vec![],
+        }];
+        assert!(config.validate().is_err());
+
+        config.targets[0].fan_groups[0].target_fans = TargetFans::TargetFans(vec![1]);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_control_mode_defaults_to_step() -> Result<()> {
+        let mut temp_file = NamedTempFile::new()?;
+        write!(
+            temp_file,
+            r#"
+run_period_seconds = 60
+
+[[targets]]
+host = "192.168.1.100"
+user = "admin"
+password_base64 = "cGFzc3dvcmQ="
+
+[targets.target_fans]
+NumFans = 2
+
+[[targets.temperature_fan_config]]
+min_temp = 0
+max_temp = 100
+max_fan_speed = 50
+"#
+        )?;
+
+        let config = IloConfig::from_toml_file(temp_file.path())?;
+        assert_eq!(config.shutdown_fan_speed, 100);
+        assert_eq!(config.max_backoff_seconds, 300);
+        assert_eq!(config.force_refresh_seconds, 600);
+        assert_eq!(config.failsafe, FailsafeConfig::default());
+        assert_eq!(config.verification, VerificationConfig::default());
+        assert!(!config.verification.enabled);
+        assert_eq!(config.targets[0].control_mode, ControlMode::Step);
+        assert_eq!(config.targets[0].hysteresis, 0);
+        assert_eq!(config.targets[0].fan_failure_compensation, 20);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_control_mode_round_trip() -> Result<()> {
+        let config = create_valid_config();
+        let temp_file = write_config_to_temp_file(&config)?;
+
+        let loaded_config = IloConfig::from_toml_file(temp_file.path())?;
+        assert_eq!(loaded_config.targets[0].control_mode, ControlMode::Step);
+        assert_eq!(loaded_config.targets[1].control_mode, ControlMode::Linear);
+
+        Ok(())
+    }
+
+    fn create_pid_config() -> PidConfig {
+        PidConfig {
+            target_temp: 60,
+            kp: 4.0,
+            ki: 0.05,
+            kd: 0.0,
+            min_fan_speed: 10,
+            max_fan_speed: 100,
+        }
+    }
+
+    #[test]
+    fn test_pid_mode_validation() {
+        let mut config = create_valid_config();
+        config.targets[0].control_mode = ControlMode::Pid;
+        // Pid mode without pid section is invalid
+        assert!(config.validate().is_err());
+
+        config.targets[0].pid = Some(create_pid_config());
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_invalid_pid_config_validation() {
+        let mut config = create_valid_config();
+        config.targets[0].control_mode = ControlMode::Pid;
+
+        let mut pid = create_pid_config();
+        pid.min_fan_speed = 80;
+        pid.max_fan_speed = 40;
+        config.targets[0].pid = Some(pid);
+        assert!(config.validate().is_err());
+
+        let mut pid = create_pid_config();
+        pid.kp = -1.0;
+        config.targets[0].pid = Some(pid);
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_pid_config_round_trip() -> Result<()> {
+        let mut config = create_valid_config();
+        config.targets[0].control_mode = ControlMode::Pid;
+        config.targets[0].pid = Some(create_pid_config());
+        let temp_file = write_config_to_temp_file(&config)?;
+
+        let loaded_config = IloConfig::from_toml_file(temp_file.path())?;
+        assert_eq!(loaded_config.targets[0].control_mode, ControlMode::Pid);
+        assert_eq!(loaded_config.targets[0].pid, Some(create_pid_config()));
+        assert_eq!(loaded_config.targets[1].pid, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sensor_selectors() -> Result<()> {
+        let config = create_valid_config();
+        let temp_file = write_config_to_temp_file(&config)?;
+
+        let loaded_config = IloConfig::from_toml_file(temp_file.path())?;
+        assert!(loaded_config.targets[0].sensors.is_empty());
+        assert_eq!(loaded_config.targets[1].sensors, config.targets[1].sensors);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_sensor_regex() {
+        assert!(SensorRegex::new("PCI (").is_err());
+
+        // 不正なパターンは読み込み時に拒否される
+        let curve: FanCurve = toml::from_str(r#"sensors = [{ Regex = '^\d+-PCI' }]"#).unwrap();
+        assert_eq!(curve.sensors.len(), 1);
+        let error = toml::from_str::<FanCurve>("sensors = [{ Regex = 'PCI (' }]").unwrap_err();
+        assert!(error.to_string().contains("Invalid sensor regex 'PCI ('"));
+    }
+
+    fn create_pci_curve() -> FanCurve {
+        FanCurve {
+            sensors: vec![SensorSelector::Name("PCI 1 Zone".to_string())],
+            control_mode: ControlMode::Linear,
+            hysteresis: 0,
+            temperature_fan_config: vec![
+                FanConfig {
+                    min_temp: 40,
+                    max_temp: 60,
+                    max_fan_speed: 20,
+                    min_fan_speed: None,
+                },
+                FanConfig {
+                    min_temp: 70,
+                    max_temp: 100,
+                    max_fan_speed: 100,
+                    min_fan_speed: None,
+                },
+            ],
+            pid: None,
+        }
+    }
+
+    #[test]
+    fn test_fan_curves() -> Result<()> {
+        let mut config = create_valid_config();
+        config.targets[0].curves = vec![create_pci_curve()];
+        let temp_file = write_config_to_temp_file(&config)?;
+
+        let loaded_config = IloConfig::from_toml_file(temp_file.path())?;
+        let groups = loaded_config.targets[0].fan_groups();
+        assert_eq!(groups.len(), 1);
+        let curves = groups[0].fan_curves();
+        assert_eq!(curves.len(), 2);
+        assert_eq!(curves[0], config.targets[0].primary_curve());
+        assert_eq!(curves[1], create_pci_curve());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fan_groups() -> Result<()> {
+        let mut config = create_valid_config();
+        config.targets[0].fan_groups = vec![FanGroup {
+            target_fans: TargetFans::TargetFans(vec![5, 6]),
+            curve: create_pci_curve(),
+            curves: vec![],
+        }];
+        let temp_file = write_config_to_temp_file(&config)?;
+
+        let loaded_config = IloConfig::from_toml_file(temp_file.path())?;
+        let groups = loaded_config.targets[0].fan_groups();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].target_fans, TargetFans::NumFans(3));
+        assert_eq!(groups[1], config.targets[0].fan_groups[0]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fan_groups_only() -> Result<()> {
+        let mut temp_file = NamedTempFile::new()?;
+        write!(
+            temp_file,
+            r#"
+run_period_seconds = 60
+
+[[targets]]
+host = "192.168.1.100"
+user = "admin"
+password_base64 = "cGFzc3dvcmQ="
+
+[[targets.fan_groups]]
+target_fans = {{ TargetFans = [1, 2] }}
+sensors = [{{ PhysicalContext = "CPU" }}]
+control_mode = "Linear"
+hysteresis = 2
+
+[[targets.fan_groups.temperature_fan_config]]
+min_temp = 40
+max_temp = 60
+max_fan_speed = 20
+
+[[targets.fan_groups]]
+target_fans = {{ TargetFans = [5, 6] }}
+sensors = [{{ Name = "PCI 1 Zone" }}]
+
+[[targets.fan_groups.temperature_fan_config]]
+min_temp = 0
+max_temp = 100
+max_fan_speed = 30
+"#
+        )?;
+
+        let config = IloConfig::from_toml_file(temp_file.path())?;
+        let target = &config.targets[0];
+        assert_eq!(target.target_fans, TargetFans::TargetFans(vec![]));
+        assert_eq!(target.fan_groups.len(), 2);
+        assert_eq!(target.fan_groups[0].curve.control_mode, ControlMode::Linear);
+        assert_eq!(target.fan_groups[0].curve.hysteresis, 2);
+        assert_eq!(
+            target.fan_groups[1].curve.temperature_fan_config[0].max_fan_speed,
+            30
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_fan_group_validation() {
+        let mut config = create_valid_config();
+        let mut curve = create_pci_curve();
+        curve.control_mode = ControlMode::Pid;
+        config.targets[0].fan_groups = vec![FanGroup {
+            target_fans: TargetFans::TargetFans(vec![5, 6]),
+            curve,
+            curves: vec![],
+        }];
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_invalid_fan_curve_validation() {
+        let mut config = create_valid_config();
+        let mut curve = create_pci_curve();
+        curve.control_mode = ControlMode::Pid;
+        config.targets[0].curves = vec![curve];
+        assert!(config.validate().is_err());
+
+        let mut curve = create_pci_curve();
+        curve.temperature_fan_config[0].max_fan_speed = 120;
+        config.targets[0].curves = vec![curve];
+        assert!(config.validate().is_err());
+    }
+
     #[test]
     fn test_invalid_file_path() {
         let result = IloConfig::from_toml_file("/nonexistent/path/config.toml");
@@ -1,10 +1,16 @@
 use anyhow::Result;
-use base64::prelude::BASE64_STANDARD;
-use base64::Engine as _;
 use log::{debug, info};
+use serde::{Deserialize, Serialize};
 use std::fmt::{self};
 
-#[derive(Debug, PartialEq)]
+use crate::config::SensorSelector;
+use crate::redfish::RedfishClient;
+use crate::thermal::Thermal;
+
+/// Path of the Redfish `Thermal` resource
+const THERMAL_PATH: &str = "/redfish/v1/Chassis/1/Thermal/";
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct CpuTemp {
     pub cpuid: u8,
     pub current: u8,
@@ -16,11 +22,91 @@ impl fmt::Display for CpuTemp {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Sensor {
+    pub name: String,
+    pub number: Option<u32>,
+    pub physical_context: String,
+    /// Reading in Celsius, `None` for absent or disabled sensors
+    pub current: Option<u8>,
+    pub caution: Option<u8>,
+    pub critical: Option<u8>,
+    pub fatal: Option<u8>,
+    #[serde(default)]
+    pub health: String,
+}
+
+impl fmt::Display for Sensor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let threshold = |threshold: Option<u8>| {
+            threshold
+                .map(|threshold| format!("{}°C", threshold))
+                .unwrap_or_else(|| "-".to_string())
+        };
+
+        write!(
+            f,
+            "{} ({})\tTemperature:\t{}\tCaution/Critical/Fatal:\t{}/{}/{}\tHealth:\t{}",
+            self.name,
+            self.physical_context,
+            threshold(self.current),
+            threshold(self.caution),
+            threshold(self.critical),
+            threshold(self.fatal),
+            self.health
+        )
+    }
+}
+
+impl Sensor {
+    /// Checks whether the sensor needs attention
+    ///
+    /// # Returns
+    /// * `bool` - `true` if the health is neither "OK" nor "Absent", or the
+    ///   reading reached the lowest threshold of the sensor
+    pub fn needs_attention(&self) -> bool {
+        let lowest_threshold = [self.caution, self.critical, self.fatal]
+            .into_iter()
+            .flatten()
+            .min();
+
+        !matches!(self.health.as_str(), "OK" | "Absent")
+            || lowest_threshold
+                .zip(self.current)
+                .is_some_and(|(threshold, current)| current >= threshold)
+    }
+
+    /// Checks whether the sensor is selected by a sensor selector
+    ///
+    /// `Name` matches either the full sensor name (e.g. `31-PCI 1 Zone`) or
+    /// the name without its numeric prefix (e.g. `PCI 1 Zone`).
+    ///
+    /// # Arguments
+    /// * `selector` - Sensor selector from the configuration
+    ///
+    /// # Returns
+    /// * `bool` - `true` if the sensor is selected
+    pub fn matches(&self, selector: &SensorSelector) -> bool {
+        match selector {
+            SensorSelector::Name(name) => {
+                let short_name = self
+                    .name
+                    .split_once('-')
+                    .filter(|(prefix, _)| prefix.chars().all(|c| c.is_ascii_digit()))
+                    .map(|(_, short_name)| short_name);
+                self.name == *name || short_name == Some(name.as_str())
+            }
+            SensorSelector::Regex(regex) => regex.is_match(&self.name),
+            SensorSelector::PhysicalContext(context) => self.physical_context == *context,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Fan {
-    name: String,
-    current: u8,
-    status: String,
+    pub name: String,
+    pub current: u8,
+    pub status: String,
 }
 
 impl fmt::Display for Fan {
@@ -33,13 +119,135 @@ impl fmt::Display for Fan {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct TempData {
     pub cpu_temps: Vec<CpuTemp>,
     pub high_temp_critical_reached_component: bool,
     pub high_temp_component_name: Vec<String>,
     pub num_fans: u8,
     pub fans: Vec<Fan>,
+    pub sensors: Vec<Sensor>,
+}
+
+impl TempData {
+    /// Returns the highest temperature of the selected sensors
+    ///
+    /// Sensors without a reading are skipped.
+    ///
+    /// # Arguments
+    /// * `selectors` - Sensor selectors from the configuration; an empty
+    ///   list selects the CPU sensors
+    ///
+    /// # Returns
+    /// * `Option<u8>` - Highest temperature, or `None` if no sensor matched
+    pub fn max_temperature(&self, selectors: &[SensorSelector]) -> Option<u8> {
+        let default_selectors = [SensorSelector::PhysicalContext("CPU".to_string())];
+        let selectors = if selectors.is_empty() {
+            &default_selectors[..]
+        } else {
+            selectors
+        };
+
+        self.sensors
+            .iter()
+            .filter(|sensor| selectors.iter().any(|selector| sensor.matches(selector)))
+            .filter_map(|sensor| sensor.current)
+            .max()
+    }
+
+    /// Writes all readings as CSV
+    ///
+    /// Every CPU, sensor and fan becomes one row with the columns
+    /// `kind,name,physical_context,value,status,caution,critical,fatal`.
+    /// Temperatures are in Celsius and fan speeds in percent; the thresholds
+    /// are only filled in for sensors.
+    ///
+    /// # Returns
+    /// * `Result<String>` - CSV text including the header row
+    pub fn to_csv(&self) -> Result<String> {
+        #[derive(Serialize)]
+        struct Row<'a> {
+            kind: &'a str,
+            name: &'a str,
+            physical_context: &'a str,
+            value: Option<u8>,
+            status: &'a str,
+            caution: Option<u8>,
+            critical: Option<u8>,
+            fatal: Option<u8>,
+        }
+
+        let mut writer = csv::Writer::from_writer(Vec::new());
+
+        for cpu in &self.cpu_temps {
+            writer.serialize(Row {
+                kind: "cpu",
+                name: &format!("CPU {}", cpu.cpuid),
+                physical_context: "CPU",
+                value: Some(cpu.current),
+                status: "",
+                caution: None,
+                critical: None,
+                fatal: None,
+            })?;
+        }
+        for sensor in &self.sensors {
+            let critical = self.high_temp_component_name.contains(&sensor.name);
+            writer.serialize(Row {
+                kind: "sensor",
+                name: &sensor.name,
+                physical_context: &sensor.physical_context,
+                value: sensor.current,
+                status: if critical { "Critical" } else { &sensor.health },
+                caution: sensor.caution,
+                critical: sensor.critical,
+                fatal: sensor.fatal,
+            })?;
+        }
+        for fan in &self.fans {
+            writer.serialize(Row {
+                kind: "fan",
+                name: &fan.name,
+                physical_context: "",
+                value: Some(fan.current),
+                status: &fan.status,
+                caution: None,
+                critical: None,
+                fatal: None,
+            })?;
+        }
+
+        Ok(String::from_utf8(writer.into_inner()?)?)
+    }
+
+    /// Returns the sensors that need attention
+    ///
+    /// # Returns
+    /// * `Vec<&Sensor>` - Sensors that report a problem or reached one of
+    ///   their thresholds
+    pub fn sensors_needing_attention(&self) -> Vec<&Sensor> {
+        self.sensors
+            .iter()
+            .filter(|sensor| sensor.needs_attention())
+            .collect()
+    }
+
+    /// Returns the fans that failed or are degraded
+    ///
+    /// A fan counts as failed when its health is not "OK", or when it stands
+    /// still while other fans are spinning. Empty fan bays, reported as
+    /// "Absent", are not failed fans.
+    ///
+    /// # Returns
+    /// * `Vec<&Fan>` - Failed fans in the order reported by the iLO
+    pub fn failed_fans(&self) -> Vec<&Fan> {
+        let spinning = self.fans.iter().any(|fan| fan.current > 0);
+        self.fans
+            .iter()
+            .filter(|fan| fan.status != "Absent")
+            .filter(|fan| fan.status != "OK" || (spinning && fan.current == 0))
+            .collect()
+    }
 }
 
 impl fmt::Display for TempData {
@@ -66,11 +274,24 @@ impl fmt::Display for TempData {
             writeln!(f, " - {}", cpu)?;
         }
 
+        writeln!(f, "\nSensor Temperatures:")?;
+        for sensor in &self.sensors {
+            writeln!(f, " - {}", sensor)?;
+        }
+
         writeln!(f, "\nFan Status (Count: {}):", self.num_fans)?;
         for fan in &self.fans {
             writeln!(f, " - {}", fan)?;
         }
 
+        let failed_fans = self.failed_fans();
+        if !failed_fans.is_empty() {
+            writeln!(f, "\nFailed fans:")?;
+            for fan in failed_fans {
+                writeln!(f, " - {}", fan)?;
+            }
+        }
+
         Ok(())
     }
 }
@@ -78,61 +299,26 @@ impl fmt::Display for TempData {
 /// Retrieves temperature and fan data from an ILO interface
 ///
 /// # Arguments
-/// * `address` - The hostname or IP address of the ILO interface
+/// * `client` - Redfish client of the ILO, logged in on first use
 ///
 /// # Returns
 /// * `Result<TempData>` - Temperature and fan data or an error
 ///
-/// This function makes an HTTPS request to the ILO's Redfish API endpoint
-/// to get current temperature and fan status information.
-pub async fn get_temp_data(address: &str, user: &str, password_base64: &str) -> Result<TempData> {
-    debug!("Getting temperature data from ILO at {}@{}", user, address);
-    debug!("Password is set: {}", !password_base64.is_empty());
-
-    let password = BASE64_STANDARD
-        .decode(password_base64.as_bytes())
-        .unwrap_or_else(|_| password_base64.as_bytes().to_vec());
-
-    let password = String::from_utf8(password)?;
-
-    // remove \n and \r
-    let password = password
-        .chars()
-        .filter(|&c| c != '\n' && c != '\r')
-        .collect::<String>();
-
-    let url = format!("https://{address}/redfish/v1/Chassis/1/Thermal/");
-    info!("Fetching temperature data from ILO at {}", url);
-    let json = get_ilo_data(&url, user, &password).await?;
+/// This function reads the ILO's Redfish `Thermal` resource to get current
+/// temperature and fan status information. The session and connections of
+/// the client are reused by the following calls.
+pub async fn get_temp_data(client: &mut RedfishClient) -> Result<TempData> {
+    info!(
+        "Fetching temperature data from ILO at https://{}{}",
+        client.address(),
+        THERMAL_PATH
+    );
+    let json = client.get(THERMAL_PATH).await?;
     debug!("JSON response: {}", json);
     let temp_data = json_parser(&json)?;
     Ok(temp_data)
 }
 
-/// Makes an HTTPS request to an ILO interface
-///
-/// # Arguments
-/// * `url` - The complete URL to request from the ILO interface
-///
-/// # Returns
-/// * `Result<String>` - The response body as a string or an error
-///
-/// This function creates an HTTPS client that accepts self-signed certificates
-/// and makes a GET request to the specified URL.
-async fn get_ilo_data(url: &str, user: &str, password: &str) -> Result<String> {
-    let client = reqwest::Client::builder()
-        .danger_accept_invalid_certs(true)
-        .build()?;
-    let resp = client
-        .get(url)
-        .basic_auth(user, Some(password))
-        .send()
-        .await?
-        .text()
-        .await?;
-    Ok(resp)
-}
-
 /// Parses JSON response from ILO into TempData structure
 ///
 /// # Arguments
@@ -141,162 +327,102 @@ async fn get_ilo_data(url: &str, user: &str, password: &str) -> Result<String> {
 /// # Returns
 /// * `Result<TempData>` - Parsed temperature and fan data or an error
 ///
-/// This function extracts relevant temperature and fan information from
-/// the ILO's JSON response and organizes it into a TempData structure.
+/// This function deserializes the ILO's Redfish `Thermal` resource and
+/// organizes the relevant temperature and fan information into a TempData
+/// structure.
 fn json_parser(json: &str) -> Result<TempData> {
-    let data: serde_json::Value = serde_json::from_str(json)?;
-
-    // Get fan data
-    let fan_data = match data.get("Fans") {
-        None => {
-            return Err(anyhow::anyhow!("No fan data found"));
-        }
-        Some(fan_data_raw) => fan_data_raw.as_array(),
-    };
-    // Get the array from
-    // fan data to struct
-
-    let mut fans: Vec<Fan> = Vec::new();
+    let thermal: Thermal = serde_json::from_str(json)?;
 
-    match fan_data {
-        None => {
-            return Err(anyhow::anyhow!("No fan data found"));
-        }
-        Some(fan_data) => {
-            for fan in fan_data {
-                debug!("Fan data: {:?}", fan);
-                let fan_name = match fan.get("FanName") {
-                    None => {
-                        return Err(anyhow::anyhow!("No fan name found"));
-                    }
-                    Some(fan_name) => fan_name.as_str(),
-                };
-                debug!("Fan name: {:?}", fan_name);
-                let current_reading = match fan.get("CurrentReading") {
-                    None => {
-                        return Err(anyhow::anyhow!("No current reading found"));
-                    }
-                    Some(current_reading) => current_reading.as_u64(),
-                };
-                debug!("Fan current reading: {:?}", current_reading);
-                let unknown = serde_json::Value::String("Unknown".into());
-                let status = match fan.get("Status") {
-                    None => {
-                        return Err(anyhow::anyhow!("No status found"));
-                    }
-                    Some(status) => status
-                        .get("Health")
-                        .unwrap_or_else(|| status.get("State").unwrap_or(&unknown))
-                        .as_str(),
-                };
-                debug!("Fan status: {:?}", status);
-                let fan = Fan {
-                    name: fan_name.unwrap().to_string(),
-                    current: current_reading.unwrap() as u8,
-                    status: status.unwrap().to_string(),
-                };
-                debug!("Fan: {:?}", fan);
-                fans.push(fan);
-            }
-        }
+    if thermal.temperatures.is_empty() {
+        return Err(anyhow::anyhow!("No temperature data found"));
     }
 
-    // cpu temp data to struct
-    let temperatures = match data.get("Temperatures") {
-        None => {
-            return Err(anyhow::anyhow!("No temperature data found"));
-        }
-        Some(temperatures) => temperatures.as_array().unwrap(),
-    };
-
-    let mut high_temp_critical_reached_component = false;
-    let mut high_temp_component_name: Vec<String> = Vec::new();
-
-    // check if high temp critical reached
-    for temp in temperatures {
-        // Get the current reading
-        let current_reading = match temp.get("CurrentReading") {
-            None => {
-                return Err(anyhow::anyhow!("No current reading found"));
-            }
-            Some(current_reading) => current_reading.as_u64(),
-        };
+    Ok(TempData::from(&thermal))
+}
 
-        //get UpperThresholdCritical
-        let upper_threshold_critical = match temp.get("UpperThresholdCritical") {
-            None => {
-                return Err(anyhow::anyhow!("No UpperThresholdCritical found"));
-            }
-            Some(upper_threshold_critical) => upper_threshold_critical.as_u64(),
-        };
-        let current_component_name = match temp.get("Name") {
-            None => {
-                return Err(anyhow::anyhow!("No component name found"));
-            }
-            Some(current_component_name) => current_component_name.as_str(),
-        };
+impl From<&Thermal> for TempData {
+    /// Converts the Redfish `Thermal` resource to `TempData`
+    ///
+    /// Sensors without a reading are kept without a temperature, so absent
+    /// and disabled sensors still show up with their state. Fans without a
+    /// reading are reported at 0%. The caution threshold is `UpperThresholdNonCritical`,
+    /// or the user defined `UpperThresholdUser` of iLO4.
+    fn from(thermal: &Thermal) -> Self {
+        let fans: Vec<Fan> = thermal
+            .fans
+            .iter()
+            .map(|fan| Fan {
+                name: fan.fan_name.clone().unwrap_or_default(),
+                current: to_u8(fan.current_reading.unwrap_or_default()),
+                status: fan.health().to_string(),
+            })
+            .collect();
 
-        if current_reading.unwrap() > upper_threshold_critical.unwrap()
-            && upper_threshold_critical.unwrap() != 0
-        {
-            high_temp_critical_reached_component = true;
-            high_temp_component_name.push(current_component_name.unwrap().to_string());
-        }
-    }
+        let high_temp_component_name: Vec<String> = thermal
+            .temperatures
+            .iter()
+            .filter(|temp| temp.is_critical())
+            .map(|temp| temp.name.clone().unwrap_or_default())
+            .collect();
 
-    let mut cpu_temps: Vec<CpuTemp> = Vec::new();
+        let sensors: Vec<Sensor> = thermal
+            .temperatures
+            .iter()
+            .map(|temp| Sensor {
+                name: temp.name.clone().unwrap_or_default(),
+                number: temp.number,
+                physical_context: temp.physical_context.clone().unwrap_or_default(),
+                current: temp.reading().map(to_u8),
+                caution: threshold(
+                    temp.upper_threshold_non_critical
+                        .or(temp.upper_threshold_user),
+                ),
+                critical: threshold(temp.upper_threshold_critical),
+                fatal: threshold(temp.upper_threshold_fatal),
+                health: temp.health().to_string(),
+            })
+            .collect();
 
-    // Get the cpu temps
-    for temp in temperatures {
-        debug!("Temp data: {:?}", temp);
-        let physical_context = match temp.get("PhysicalContext") {
-            None => {
-                return Err(anyhow::anyhow!("No PhysicalContext found"));
-            }
-            Some(physical_context) => physical_context.as_str(),
-        };
+        let cpu_temps: Vec<CpuTemp> = sensors
+            .iter()
+            .filter(|sensor| sensor.physical_context == "CPU")
+            .filter_map(|sensor| {
+                Some(CpuTemp {
+                    cpuid: sensor
+                        .name
+                        .split_whitespace()
+                        .last()
+                        .and_then(|id| id.parse::<u8>().ok())
+                        .unwrap_or(1),
+                    current: sensor.current?,
+                })
+            })
+            .collect();
 
-        if physical_context.unwrap() == "CPU" {
-            let name = match temp.get("Name") {
-                None => {
-                    return Err(anyhow::anyhow!("No name found"));
-                }
-                Some(name) => name.as_str().unwrap(),
-            };
-            let current_reading = match temp.get("CurrentReading") {
-                None => {
-                    return Err(anyhow::anyhow!("No current reading found"));
-                }
-                Some(current_reading) => current_reading.as_u64().unwrap() as u8,
-            };
-
-            let cpu_temp = CpuTemp {
-                cpuid: name
-                    .split_whitespace()
-                    .last()
-                    .unwrap()
-                    .parse::<u8>()
-                    .unwrap_or(1),
-                current: current_reading,
-            };
-
-            cpu_temps.push(cpu_temp);
+        TempData {
+            cpu_temps,
+            high_temp_critical_reached_component: !high_temp_component_name.is_empty(),
+            high_temp_component_name,
+            num_fans: fans.len() as u8,
+            fans,
+            sensors,
         }
     }
+}
 
-    let temp_data: TempData = TempData {
-        cpu_temps,
-        high_temp_critical_reached_component,
-        high_temp_component_name,
-        num_fans: fans.len() as u8,
-        fans,
-    };
-    Ok(temp_data)
+/// Converts a threshold to Celsius, treating 0 as "no threshold"
+fn threshold(threshold: Option<f64>) -> Option<u8> {
+    threshold.filter(|threshold| *threshold > 0.0).map(to_u8)
+}
+
+/// Converts a reading to a whole number clamped to 0-255
+fn to_u8(reading: f64) -> u8 {
+    reading.round().clamp(0.0, u8::MAX as f64) as u8
 }
 
 #[cfg(test)]
-mod test {
-    const ILO_JSON: &str = r###"
+pub(crate) mod test {
+    pub(crate) const ILO_JSON: &str = r###"
 {
   "@odata.context": "/redfish/v1/$metadata#Chassis/Members/1/Thermal$entity",
   "@odata.id": "/redfish/v1/Chassis/1/Thermal/",
@@ -1238,6 +1364,7 @@ mod test {
 
 "###;
     use super::*;
+    use crate::config::SensorRegex;
 
     #[test]
     fn test_json_parser() {
@@ -1298,12 +1425,29 @@ mod test {
             },
         ];
 
+        let sensors = super::json_parser(ILO_JSON).unwrap().sensors;
+        assert_eq!(sensors.len(), 37);
+        assert_eq!(
+            sensors[0],
+            super::Sensor {
+                name: "01-Inlet Ambient".to_string(),
+                number: Some(1),
+                physical_context: "Intake".to_string(),
+                current: Some(23),
+                caution: None,
+                critical: Some(42),
+                fatal: Some(46),
+                health: "OK".to_string(),
+            }
+        );
+
         let temp_data: super::TempData = super::TempData {
             cpu_temps,
             high_temp_critical_reached_component: false,
             high_temp_component_name: vec![],
             num_fans: 7,
             fans,
+            sensors,
         };
         println!("temp_data: {:#?}", temp_data);
         println!("Result_data: {:#?}", result.as_ref().unwrap());
@@ -1311,38 +1455,172 @@ mod test {
         assert_eq!(result.unwrap(), temp_data);
     }
 
-    #[tokio::test]
-    async fn test_async_function() {
-        use std::path::Path;
-        use std::process::Command;
-        use std::thread::sleep;
-        use std::time::Duration;
-
-        // Start the test server as a background process
-        println!("Starting test HTTPS server...");
-        let server_dir = Path::new("./test-https-server");
-        let mut server_process = Command::new("python3")
-            .arg("runserver.py")
-            .current_dir(server_dir)
-            .spawn()
-            .expect("Failed to start test server");
-
-        // Give the server a moment to start
-        sleep(Duration::from_secs(2));
-
-        // Run the actual test
-        let result = get_ilo_data("https://localhost:8080", "user", "password").await;
-        println!("Result: {:#?}", result);
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), "Hello-World-Test");
-
-        // Clean up: kill the server process and wait for it to finish
-        server_process
-            .kill()
-            .expect("Failed to kill server process");
-        server_process
-            .wait()
-            .expect("Failed to wait for server process");
-        println!("Test server stopped");
+    #[test]
+    fn test_json_parser_missing_fields() {
+        let json = r#"{
+            "Fans": [{"FanName": "Fan 1", "CurrentReading": null, "Status": {"State": "Absent"}}],
+            "Temperatures": [
+                {"Name": "01-Inlet Ambient", "CurrentReading": 21, "PhysicalContext": "Intake"},
+                {"Name": "02-CPU 1", "CurrentReading": 41.6, "PhysicalContext": "CPU"},
+                {"Name": "03-P1 DIMM 1-6", "CurrentReading": null, "PhysicalContext": "SystemBoard", "Status": {"State": "Absent"}}
+            ]
+        }"#;
+
+        let temp_data = super::json_parser(json).unwrap();
+        // 読み取り値のないセンサーも状態を表示するために残す
+        assert_eq!(temp_data.sensors.len(), 3);
+        assert_eq!(temp_data.sensors[2].name, "03-P1 DIMM 1-6");
+        assert_eq!(temp_data.sensors[2].current, None);
+        // 制御では読み取り値のないセンサーを無視する
+        let dimm = [SensorSelector::Name("P1 DIMM 1-6".to_string())];
+        assert_eq!(temp_data.max_temperature(&dimm), None);
+        assert_eq!(temp_data.sensors[2].health, "Absent");
+        assert!(!temp_data.sensors[2].needs_attention());
+        assert_eq!(temp_data.cpu_temps.len(), 1);
+        assert_eq!(temp_data.cpu_temps[0].cpuid, 1);
+        assert_eq!(temp_data.cpu_temps[0].current, 42);
+        assert_eq!(temp_data.fans[0].current, 0);
+        assert_eq!(temp_data.fans[0].status, "Absent");
+        assert!(!temp_data.high_temp_critical_reached_component);
+
+        assert!(super::json_parser(r#"{"error": {"code": "Unauthorized"}}"#).is_err());
+    }
+
+    #[test]
+    fn test_max_temperature() {
+        let temp_data = super::json_parser(ILO_JSON).unwrap();
+
+        // Default selects the CPU sensors
+        assert_eq!(temp_data.max_temperature(&[]), Some(47));
+
+        assert_eq!(
+            temp_data.max_temperature(&[SensorSelector::Name("PCI 1 Zone".to_string())]),
+            Some(40)
+        );
+        assert_eq!(
+            temp_data.max_temperature(&[SensorSelector::Name("31-PCI 1 Zone".to_string())]),
+            Some(40)
+        );
+        assert_eq!(
+            temp_data.max_temperature(&[SensorSelector::PhysicalContext("Intake".to_string())]),
+            Some(29)
+        );
+        assert_eq!(
+            temp_data.max_temperature(&[
+                SensorSelector::Regex(SensorRegex::new("^0[45]-P1 DIMM").unwrap()),
+                SensorSelector::Name("01-Inlet Ambient".to_string()),
+            ]),
+            Some(38)
+        );
+        assert_eq!(
+            temp_data.max_temperature(&[SensorSelector::Name("GPU".to_string())]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_serialize_temp_data() {
+        let temp_data = super::json_parser(ILO_JSON).unwrap();
+
+        let json = serde_json::to_string(&temp_data).unwrap();
+        assert_eq!(
+            serde_json::from_str::<super::TempData>(&json).unwrap(),
+            temp_data
+        );
+
+        let yaml = serde_yaml_ng::to_string(&temp_data).unwrap();
+        assert_eq!(
+            serde_yaml_ng::from_str::<super::TempData>(&yaml).unwrap(),
+            temp_data
+        );
+
+        let csv = temp_data.to_csv().unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            lines[0],
+            "kind,name,physical_context,value,status,caution,critical,fatal"
+        );
+        assert_eq!(
+            lines.len(),
+            1 + temp_data.cpu_temps.len() + temp_data.sensors.len() + temp_data.fans.len()
+        );
+        assert!(lines.contains(&"sensor,01-Inlet Ambient,Intake,23,OK,,42,46"));
+        assert!(lines.contains(&"fan,Fan 1,,11,OK,,,"));
+    }
+
+    #[test]
+    fn test_failed_fans() {
+        let mut temp_data = super::json_parser(ILO_JSON).unwrap();
+        assert!(temp_data.failed_fans().is_empty());
+
+        // Unhealthy fan
+        temp_data.fans[1].status = "Critical".to_string();
+        // Fan standing still while the others spin
+        temp_data.fans[3].current = 0;
+
+        let failed: Vec<&str> = temp_data
+            .failed_fans()
+            .iter()
+            .map(|fan| fan.name.as_str())
+            .collect();
+        assert_eq!(failed, vec!["Fan 2", "Fan 4"]);
+        assert!(temp_data.to_string().contains("Failed fans:"));
+
+        // Fans standing still together are not reported as failed
+        for fan in temp_data.fans.iter_mut() {
+            fan.current = 0;
+            fan.status = "OK".to_string();
+        }
+        assert!(temp_data.failed_fans().is_empty());
+    }
+
+    #[test]
+    fn test_failed_fans_absent_bay() {
+        // 空のファンベイは故障として扱わない
+        let temp_data = super::json_parser(
+            r#"{
+                "Fans": [
+                    {"FanName": "Fan 1", "CurrentReading": 30, "Status": {"Health": "OK", "State": "Enabled"}},
+                    {"FanName": "Fan 2", "CurrentReading": 0, "Status": {"State": "Absent"}},
+                    {"FanName": "Fan 3", "CurrentReading": 30, "Status": {"Health": "OK", "State": "Enabled"}},
+                    {"FanName": "Fan 4", "CurrentReading": 0, "Status": {"State": "Absent"}},
+                    {"FanName": "Fan 5", "CurrentReading": 0, "Status": {"Health": "Critical", "State": "Enabled"}}
+                ],
+                "Temperatures": [
+                    {"Name": "02-CPU 1", "CurrentReading": 40, "PhysicalContext": "CPU", "Status": {"Health": "OK"}}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let failed: Vec<&str> = temp_data
+            .failed_fans()
+            .iter()
+            .map(|fan| fan.name.as_str())
+            .collect();
+        assert_eq!(failed, vec!["Fan 5"]);
+
+        let mut temp_data = temp_data;
+        temp_data.fans.pop();
+        assert!(temp_data.failed_fans().is_empty());
+        assert!(!temp_data.to_string().contains("Failed fans:"));
+    }
+
+    #[test]
+    fn test_sensors_needing_attention() {
+        let mut temp_data = super::json_parser(ILO_JSON).unwrap();
+        assert!(temp_data.sensors_needing_attention().is_empty());
+
+        // Reading at the critical threshold
+        temp_data.sensors[0].current = Some(42);
+        // Sensor reporting a problem
+        temp_data.sensors[1].health = "Warning".to_string();
+
+        let names: Vec<&str> = temp_data
+            .sensors_needing_attention()
+            .iter()
+            .map(|sensor| sensor.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["01-Inlet Ambient", "02-CPU 1"]);
     }
 }
@@ -0,0 +1,332 @@
+use std::time::{Duration, Instant};
+
+use crate::config::{FailsafeConfig, VerificationConfig};
+use crate::cputemp::TempData;
+use crate::gen_ssh::{PendingVerification, VerificationOutcome};
+
+/// Failsafe state of a target
+///
+/// Tracks whether the temperatures of a target can still be trusted and
+/// whether its fans are currently forced to the failsafe speed. Readings are
+/// no longer trusted after `max_read_failures` failed reads in a row, or once
+/// the newest reading is older than `stale_after_seconds`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Failsafe {
+    /// Number of failed temperature reads in a row
+    read_failures: u32,
+    /// Time the newest successful reading was taken
+    last_read: Option<Instant>,
+    /// Whether the fans are currently forced to the failsafe speed
+    active: bool,
+}
+
+impl Failsafe {
+    /// Records a successful temperature read
+    ///
+    /// # Arguments
+    /// * `taken_at` - Time the reading was taken
+    pub fn record_read(&mut self, taken_at: Instant) {
+        self.read_failures = 0;
+        self.last_read = Some(taken_at);
+    }
+
+    /// Records a failed temperature read
+    pub fn record_read_failure(&mut self) {
+        self.read_failures += 1;
+    }
+
+    /// Returns why the readings of the target can no longer be trusted
+    ///
+    /// The age of the newest reading is checked after successful reads as
+    /// well, so old data never keeps the fans on a quiet setting.
+    ///
+    /// # Arguments
+    /// * `config` - Failsafe policy of the daemon
+    /// * `now` - Current time
+    ///
+    /// # Returns
+    /// * `Option<String>` - The reason, or `None` while the readings can
+    ///   still be trusted
+    pub fn untrusted_reason(&self, config: &FailsafeConfig, now: Instant) -> Option<String> {
+        if config.max_read_failures > 0 && self.read_failures >= config.max_read_failures {
+            return Some(format!(
+                "{} temperature reads failed in a row",
+                self.read_failures
+            ));
+        }
+
+        let stale_after = Duration::from_secs(config.stale_after_seconds as u64);
+        let age = now.saturating_duration_since(self.last_read?);
+        (config.stale_after_seconds > 0 && age >= stale_after)
+            .then(|| format!("no temperature reading for {} seconds", age.as_secs()))
+    }
+
+    /// Marks the fans as forced to the failsafe speed
+    ///
+    /// # Returns
+    /// * `bool` - `true` if the failsafe was not active before
+    pub fn enter(&mut self) -> bool {
+        !std::mem::replace(&mut self.active, true)
+    }
+
+    /// Marks the fans as following the fan curves again
+    ///
+    /// # Returns
+    /// * `bool` - `true` if the failsafe was active before
+    pub fn release(&mut self) -> bool {
+        std::mem::replace(&mut self.active, false)
+    }
+
+    /// Whether the fans are currently forced to the failsafe speed
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Returns the upper limit of the retry delay of the target
+    ///
+    /// While reads fail or the failsafe is active, the target is retried
+    /// every run period, so `max_read_failures` and `stale_after_seconds`
+    /// trip after the configured number of run periods instead of after a
+    /// growing backoff, and the fans are released as soon as readings are
+    /// back.
+    ///
+    /// # Arguments
+    /// * `period` - Run period of the daemon
+    /// * `max_backoff` - Upper limit of the retry delay otherwise
+    ///
+    /// # Returns
+    /// * `Duration` - Upper limit of the retry delay
+    pub fn max_backoff(&self, period: Duration, max_backoff: Duration) -> Duration {
+        if self.active || self.read_failures > 0 {
+            period.min(max_backoff)
+        } else {
+            max_backoff
+        }
+    }
+}
+
+/// What a run does after the temperatures of a target were read
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReadingCheck {
+    /// Force the fans to the failsafe speed for the given reason
+    Failsafe(String),
+    /// Apply the fan curves, with the outcome of the verification of the
+    /// previous commands
+    Control(VerificationOutcome),
+}
+
+/// Decides what a run does with a successful reading
+///
+/// A component at its critical temperature takes precedence over the
+/// verification of the previous commands, so fans ignoring the commands
+/// never keep the failsafe from being applied. The pending verification is
+/// dropped in that case, as the failsafe sends new commands anyway.
+///
+/// # Arguments
+/// * `failsafe` - Failsafe policy of the daemon
+/// * `verification` - Verification of applied fan speeds
+/// * `temp_data` - Readings of the current run
+/// * `pending` - Fan speeds applied by the previous run
+///
+/// # Returns
+/// * `ReadingCheck` - Whether to enter the failsafe or apply the fan curves
+pub fn check_reading(
+    failsafe: &FailsafeConfig,
+    verification: &VerificationConfig,
+    temp_data: &TempData,
+    pending: &mut PendingVerification,
+) -> ReadingCheck {
+    if failsafe.on_critical && temp_data.high_temp_critical_reached_component {
+        pending.clear();
+        return ReadingCheck::Failsafe(format!(
+            "critical temperature reached on {}",
+            temp_data.high_temp_component_name.join(", ")
+        ));
+    }
+
+    if !verification.enabled {
+        return ReadingCheck::Control(VerificationOutcome::Verified);
+    }
+    ReadingCheck::Control(pending.check(
+        temp_data,
+        verification.tolerance as f32,
+        verification.retries,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backoff::Backoff;
+
+    fn config() -> FailsafeConfig {
+        FailsafeConfig {
+            fan_speed: 100,
+            on_critical: true,
+            max_read_failures: 3,
+            stale_after_seconds: 600,
+        }
+    }
+
+    #[test]
+    fn test_enter_on_read_failures() {
+        // 連続した読み取り失敗が上限に達するとフェイルセーフに入る
+        let config = config();
+        let now = Instant::now();
+        let mut failsafe = Failsafe::default();
+        failsafe.record_read(now);
+
+        failsafe.record_read_failure();
+        failsafe.record_read_failure();
+        assert_eq!(failsafe.untrusted_reason(&config, now), None);
+        failsafe.record_read_failure();
+        assert_eq!(
+            failsafe.untrusted_reason(&config, now).as_deref(),
+            Some("3 temperature reads failed in a row")
+        );
+        assert!(failsafe.enter());
+        assert!(!failsafe.enter());
+        assert!(failsafe.is_active());
+
+        // 0 ではチェックしない
+        let disabled = FailsafeConfig {
+            max_read_failures: 0,
+            ..config
+        };
+        assert_eq!(failsafe.untrusted_reason(&disabled, now), None);
+    }
+
+    #[test]
+    fn test_enter_on_stale_readings() {
+        // 最新の読み取りが古すぎるとフェイルセーフに入る
+        let config = config();
+        let now = Instant::now();
+        let mut failsafe = Failsafe::default();
+        assert_eq!(failsafe.untrusted_reason(&config, now), None);
+
+        failsafe.record_read(now);
+        let later = now + Duration::from_secs(599);
+        assert_eq!(failsafe.untrusted_reason(&config, later), None);
+
+        // 読み取りに成功していてもデータが古ければ信用しない
+        let later = now + Duration::from_secs(600);
+        assert_eq!(
+            failsafe.untrusted_reason(&config, later).as_deref(),
+            Some("no temperature reading for 600 seconds")
+        );
+
+        let disabled = FailsafeConfig {
+            stale_after_seconds: 0,
+            ..config
+        };
+        assert_eq!(failsafe.untrusted_reason(&disabled, later), None);
+    }
+
+    #[test]
+    fn test_release_after_fresh_reading() {
+        // 新しい読み取りが得られるとフェイルセーフを解除できる
+        let config = config();
+        let now = Instant::now();
+        let mut failsafe = Failsafe::default();
+        for _ in 0..3 {
+            failsafe.record_read_failure();
+        }
+        assert!(failsafe.untrusted_reason(&config, now).is_some());
+        assert!(failsafe.enter());
+
+        failsafe.record_read(now);
+        assert_eq!(failsafe.untrusted_reason(&config, now), None);
+        assert!(failsafe.release());
+        assert!(!failsafe.release());
+        assert!(!failsafe.is_active());
+    }
+
+    #[test]
+    fn test_enter_after_read_failures_in_run_periods() {
+        // 読み取り失敗ではバックオフが伸びず、max_read_failures 周期で発動する
+        let config = config();
+        let period = Duration::from_secs(60);
+        let max_backoff = Duration::from_secs(300);
+        let start = Instant::now();
+        let mut failsafe = Failsafe::default();
+        let mut backoff = Backoff::default();
+        failsafe.record_read(start);
+
+        let mut entered_at = None;
+        for run in 1..=10u32 {
+            let now = start + period * run;
+            if backoff.remaining(now).is_some() {
+                continue;
+            }
+
+            failsafe.record_read_failure();
+            if failsafe.untrusted_reason(&config, now).is_some() && failsafe.enter() {
+                entered_at = Some(run);
+            }
+            backoff.record_failure(now, period, failsafe.max_backoff(period, max_backoff));
+        }
+        assert_eq!(entered_at, Some(3));
+
+        // 読み取りが回復すれば通常のバックオフに戻る
+        failsafe.record_read(start);
+        failsafe.release();
+        assert_eq!(failsafe.max_backoff(period, max_backoff), max_backoff);
+    }
+
+    #[test]
+    fn test_critical_before_verification() {
+        // 臨界温度ではファンの検証失敗よりフェイルセーフを優先する
+        use crate::cputemp::Fan;
+        use crate::gen_ssh::FanSpeed;
+        use std::collections::BTreeMap;
+
+        let failsafe = config();
+        let verification = VerificationConfig {
+            enabled: true,
+            retries: 0,
+            ..Default::default()
+        };
+        let mut temp_data = TempData {
+            cpu_temps: vec![],
+            high_temp_critical_reached_component: true,
+            high_temp_component_name: vec!["02-CPU 1".to_string()],
+            num_fans: 1,
+            fans: vec![Fan {
+                name: "Fan 1".to_string(),
+                current: 80,
+                status: "OK".to_string(),
+            }],
+            sensors: vec![],
+        };
+        let speeds = BTreeMap::from([(
+            0,
+            FanSpeed {
+                max: 30.0,
+                min: None,
+            },
+        )]);
+        let mut pending = PendingVerification::default();
+
+        pending.expect(speeds.clone());
+        assert_eq!(
+            check_reading(&failsafe, &verification, &temp_data, &mut pending),
+            ReadingCheck::Failsafe("critical temperature reached on 02-CPU 1".to_string())
+        );
+        assert_eq!(pending, PendingVerification::default());
+
+        // 臨界温度でなければ検証の失敗を返す
+        temp_data.high_temp_critical_reached_component = false;
+        pending.expect(speeds);
+        assert!(matches!(
+            check_reading(&failsafe, &verification, &temp_data, &mut pending),
+            ReadingCheck::Control(VerificationOutcome::Failed(_))
+        ));
+
+        // 検証が無効なら確認しない
+        let disabled = VerificationConfig::default();
+        assert_eq!(
+            check_reading(&failsafe, &disabled, &temp_data, &mut pending),
+            ReadingCheck::Control(VerificationOutcome::Verified)
+        );
+    }
+}
@@ -0,0 +1,455 @@
+use anyhow::Result;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Outcome of a `fan` command sent to the iLO over SSH
+///
+/// The iLO answers every command with free text. Firmware unlocked for fan
+/// control prints nothing or `status=0` on success, while rejected commands
+/// come back with recognisable error text.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FanResponse {
+    /// The command was accepted
+    Accepted,
+    /// The firmware does not know the `fan` command (not unlocked)
+    NotRecognized(String),
+    /// The command was understood but its arguments were invalid
+    InvalidSyntax(String),
+    /// The user is not allowed to run the command
+    PermissionDenied(String),
+    /// Any other error reported by the iLO
+    Failed(String),
+}
+
+impl FanResponse {
+    /// Classifies the output of a single `fan` command
+    ///
+    /// # Arguments
+    /// * `output` - Text returned by the iLO for the command
+    ///
+    /// # Returns
+    /// * `FanResponse` - Classification of the output
+    pub fn parse(output: &str) -> FanResponse {
+        let message = output.trim().to_string();
+        let text = message.to_lowercase();
+
+        // SMASH CLP answers carry a status code, 0 meaning success
+        let status = text
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("status="))
+            .find_map(|status| status.trim().parse::<u32>().ok());
+
+        if text.contains("not recognized")
+            || text.contains("unknown command")
+            || text.contains("command not found")
+        {
+            FanResponse::NotRecognized(message)
+        } else if text.contains("insufficient privilege")
+            || text.contains("not authorized")
+            || text.contains("unauthorized")
+            || text.contains("permission denied")
+        {
+            FanResponse::PermissionDenied(message)
+        } else if text.contains("usage:")
+            || text.contains("invalid")
+            || text.contains("syntax")
+            || text.contains("bad argument")
+        {
+            FanResponse::InvalidSyntax(message)
+        } else if status.is_some_and(|status| status != 0)
+            || text.contains("error")
+            || text.contains("failed")
+        {
+            FanResponse::Failed(message)
+        } else {
+            FanResponse::Accepted
+        }
+    }
+
+    /// Whether the iLO accepted the command
+    pub fn is_accepted(&self) -> bool {
+        *self == FanResponse::Accepted
+    }
+}
+
+impl fmt::Display for FanResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FanResponse::Accepted => write!(f, "accepted"),
+            FanResponse::NotRecognized(message) => write!(
+                f,
+                "fan command not recognized, is the iLO firmware unlocked? ({})",
+                message
+            ),
+            FanResponse::InvalidSyntax(message) => write!(f, "invalid syntax ({})", message),
+            FanResponse::PermissionDenied(message) => {
+                write!(f, "permission denied ({})", message)
+            }
+            FanResponse::Failed(message) => write!(f, "failed ({})", message),
+        }
+    }
+}
+
+/// Checks that the iLO accepted every command
+///
+/// # Arguments
+/// * `commands` - Commands that were sent
+/// * `outputs` - Output of each command, in the same order
+///
+/// # Returns
+/// * `Result<()>` - An error listing every rejected command
+pub fn check_fan_responses(commands: &[String], outputs: &[String]) -> Result<()> {
+    let rejected: Vec<String> = commands
+        .iter()
+        .zip(outputs)
+        .map(|(command, output)| (command, FanResponse::parse(output)))
+        .filter(|(_, response)| !response.is_accepted())
+        .map(|(command, response)| format!("`{}`: {}", command, response))
+        .collect();
+
+    if rejected.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!(
+            "iLO rejected {} of {} fan commands: {}",
+            rejected.len(),
+            commands.len(),
+            rejected.join("; ")
+        ))
+    }
+}
+
+/// Fan settings in effect on the iLO, as listed by `fan info`
+///
+/// The output of `fan info` has a `GROUPINGS` section, which maps the PID
+/// algorithms of the sensors to the fans, followed by a `FANS` table:
+///
+/// ```text
+/// GROUPINGS
+/// 0: FASTEST Output:  36  [02*07 08*10 09*10]
+///
+/// FANS
+/// ID  STATUS  SPEED  PCT  MIN  MAX  LOCK
+/// 0   OK      36     14   10   255  0
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FanInfo {
+    /// Groupings of PID algorithms driving the fans
+    pub groupings: Vec<FanGrouping>,
+    /// Current speed and limits of every fan
+    pub fans: Vec<FanInfoEntry>,
+}
+
+/// Grouping of PID algorithms whose output drives the fans
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FanGrouping {
+    /// Number of the grouping
+    pub id: u8,
+    /// How the outputs of the PID algorithms are combined (e.g. `FASTEST`)
+    pub mode: String,
+    /// Combined output as PWM value (0-255)
+    pub output: u8,
+    /// Numbers of the PID algorithms (one per sensor) in the grouping
+    pub pids: Vec<u8>,
+}
+
+/// Speed and limits of a single fan
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FanInfoEntry {
+    /// Fan number as used by `fan p N`
+    pub id: u8,
+    /// Fan status reported by the iLO
+    pub status: String,
+    /// Current speed as PWM value (0-255)
+    pub pwm: u8,
+    /// Lower limit set with `fan p N min` (0-255)
+    pub min: u8,
+    /// Upper limit set with `fan p N max` (0-255)
+    pub max: u8,
+}
+
+impl FanInfo {
+    /// Parses the output of `fan info`
+    ///
+    /// The columns of the fan table are looked up by their header, so
+    /// additional columns of other firmware versions are ignored.
+    ///
+    /// # Arguments
+    /// * `output` - Text returned by the iLO for `fan info`
+    ///
+    /// # Returns
+    /// * `Result<FanInfo>` - Parsed fan settings or an error
+    pub fn parse(output: &str) -> Result<FanInfo> {
+        let grouping_regex = Regex::new(r"^(\d+):\s*(\S+)\s+Output:\s*(\d+)\s*\[(.*)\]")?;
+
+        let mut section = "";
+        let mut columns: Option<FanColumns> = None;
+        let mut groupings = Vec::new();
+        let mut fans = Vec::new();
+
+        for line in output.lines() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            // Section titles are lines of upper case words other than a table header
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            if !tokens[0].eq_ignore_ascii_case("ID")
+                && line
+                    .chars()
+                    .all(|c| c.is_ascii_uppercase() || c.is_whitespace())
+            {
+                section = match line {
+                    "GROUPINGS" => "GROUPINGS",
+                    "FANS" => "FANS",
+                    _ => "",
+                };
+                continue;
+            }
+
+            match section {
+                "GROUPINGS" => {
+                    if let Some(captures) = grouping_regex.captures(line) {
+                        groupings.push(FanGrouping {
+                            id: captures[1].parse()?,
+                            mode: captures[2].to_string(),
+                            output: captures[3].parse()?,
+                            pids: captures[4]
+                                .split_whitespace()
+                                .filter_map(|pid| {
+                                    let digits: String =
+                                        pid.chars().take_while(|c| c.is_ascii_digit()).collect();
+                                    digits.parse().ok()
+                                })
+                                .collect(),
+                        });
+                    }
+                }
+                "FANS" => {
+                    if tokens[0].eq_ignore_ascii_case("ID") {
+                        columns = Some(FanColumns::from_header(&tokens)?);
+                    } else if let (Some(columns), Ok(id)) = (&columns, tokens[0].parse::<u8>()) {
+                        fans.push(columns.parse_row(id, &tokens)?);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if fans.is_empty() {
+            return Err(anyhow::anyhow!(
+                "No fan table found in fan info output: {}",
+                FanResponse::parse(output)
+            ));
+        }
+
+        Ok(FanInfo { groupings, fans })
+    }
+}
+
+impl fmt::Display for FanInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Fans:")?;
+        for fan in &self.fans {
+            writeln!(
+                f,
+                " - Fan {}:\t{} ({:.0}%),\tMin: {} ({:.0}%),\tMax: {} ({:.0}%),\tStatus: {}",
+                fan.id,
+                fan.pwm,
+                pwm_to_percent(fan.pwm),
+                fan.min,
+                pwm_to_percent(fan.min),
+                fan.max,
+                pwm_to_percent(fan.max),
+                fan.status
+            )?;
+        }
+
+        writeln!(f, "\nGroupings:")?;
+        for grouping in &self.groupings {
+            let pids: Vec<String> = grouping.pids.iter().map(|pid| pid.to_string()).collect();
+            writeln!(
+                f,
+                " - Grouping {}:\t{} of PIDs [{}],\tOutput: {}",
+                grouping.id,
+                grouping.mode,
+                pids.join(", "),
+                grouping.output
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Positions of the columns of the `FANS` table
+struct FanColumns {
+    status: usize,
+    pwm: usize,
+    min: usize,
+    max: usize,
+}
+
+impl FanColumns {
+    fn from_header(header: &[&str]) -> Result<FanColumns> {
+        let find = |names: &[&str]| {
+            header
+                .iter()
+                .position(|column| {
+                    names
+                        .iter()
+                        .any(|name| column.to_uppercase().starts_with(name))
+                })
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Column {} not found in fan table header: {}",
+                        names[0],
+                        header.join(" ")
+                    )
+                })
+        };
+
+        Ok(FanColumns {
+            status: find(&["STAT"])?,
+            pwm: find(&["SPEED", "PWM"])?,
+            min: find(&["MIN"])?,
+            max: find(&["MAX"])?,
+        })
+    }
+
+    fn parse_row(&self, id: u8, row: &[&str]) -> Result<FanInfoEntry> {
+        let column = |index: usize| {
+            row.get(index)
+                .copied()
+                .ok_or_else(|| anyhow::anyhow!("Incomplete row for fan {}: {}", id, row.join(" ")))
+        };
+
+        Ok(FanInfoEntry {
+            id,
+            status: column(self.status)?.to_string(),
+            pwm: column(self.pwm)?.parse()?,
+            min: column(self.min)?.parse()?,
+            max: column(self.max)?.parse()?,
+        })
+    }
+}
+
+/// Converts a 0-255 PWM value of the iLO to a fan speed percentage
+pub fn pwm_to_percent(pwm: u8) -> f32 {
+    pwm as f32 / 2.55
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_accepted() {
+        assert_eq!(FanResponse::parse(""), FanResponse::Accepted);
+        assert_eq!(FanResponse::parse("\r\n"), FanResponse::Accepted);
+        assert_eq!(
+            FanResponse::parse("status=0\r\nstatus_tag=COMMAND COMPLETED\r\n"),
+            FanResponse::Accepted
+        );
+    }
+
+    #[test]
+    fn test_parse_rejected() {
+        let not_recognized = "status=2\r\nstatus_tag=COMMAND PROCESSING FAILED\r\n\
+                              error_tag=COMMAND NOT RECOGNIZED\r\n";
+        assert_eq!(
+            FanResponse::parse(not_recognized),
+            FanResponse::NotRecognized(not_recognized.trim().to_string())
+        );
+
+        assert!(matches!(
+            FanResponse::parse("Usage: fan p <fan> max <0-255>"),
+            FanResponse::InvalidSyntax(_)
+        ));
+        assert!(matches!(
+            FanResponse::parse("status=2\r\nerror_tag=Insufficient privilege\r\n"),
+            FanResponse::PermissionDenied(_)
+        ));
+        assert!(matches!(
+            FanResponse::parse("status=3\r\nstatus_tag=COMMAND PROCESSING FAILED\r\n"),
+            FanResponse::Failed(_)
+        ));
+    }
+
+    #[test]
+    fn test_check_fan_responses() {
+        let commands = vec!["fan p 0 max 128".to_string(), "fan p 9 max 128".to_string()];
+
+        let outputs = vec![String::new(), "status=0\r\n".to_string()];
+        assert!(check_fan_responses(&commands, &outputs).is_ok());
+
+        let outputs = vec![String::new(), "Invalid fan number".to_string()];
+        let error = check_fan_responses(&commands, &outputs).unwrap_err();
+        assert!(error.to_string().contains("1 of 2"));
+        assert!(error.to_string().contains("fan p 9 max 128"));
+    }
+
+    const FAN_INFO: &str = "\r
+GROUPINGS\r
+0: FASTEST Output:  36  [02*07 08*10 09*10 ]\r
+1: FASTEST Output:  64  [01*05 11*07]\r
+\r
+FANS\r
+ID  STATUS  SPEED  PCT  MIN  MAX  LOCK\r
+0   OK      36     14   10   102  0\r
+1   OK      64     25   10   255  0\r
+2   FAILED  0      0    51   255  0\r
+\r
+PID ALGORITHMS\r
+ID  SENSOR  OUTPUT\r
+1   01      30\r
+";
+
+    #[test]
+    fn test_parse_fan_info() {
+        let info = FanInfo::parse(FAN_INFO).unwrap();
+
+        assert_eq!(
+            info.groupings,
+            vec![
+                FanGrouping {
+                    id: 0,
+                    mode: "FASTEST".to_string(),
+                    output: 36,
+                    pids: vec![2, 8, 9],
+                },
+                FanGrouping {
+                    id: 1,
+                    mode: "FASTEST".to_string(),
+                    output: 64,
+                    pids: vec![1, 11],
+                },
+            ]
+        );
+
+        assert_eq!(info.fans.len(), 3);
+        assert_eq!(
+            info.fans[0],
+            FanInfoEntry {
+                id: 0,
+                status: "OK".to_string(),
+                pwm: 36,
+                min: 10,
+                max: 102,
+            }
+        );
+        assert_eq!(info.fans[2].status, "FAILED");
+        assert_eq!(info.fans[2].min, 51);
+
+        let json = serde_json::to_string(&info).unwrap();
+        assert_eq!(serde_json::from_str::<FanInfo>(&json).unwrap(), info);
+    }
+
+    #[test]
+    fn test_parse_fan_info_rejected() {
+        let error = FanInfo::parse("status=2\r\nerror_tag=COMMAND NOT RECOGNIZED\r\n").unwrap_err();
+        assert!(error.to_string().contains("not recognized"));
+    }
+}
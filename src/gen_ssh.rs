@@ -1,7 +1,20 @@
-use crate::config::{TargetFans, TargetIlo};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{ControlMode, FanConfig, FanCurve, TargetFans, TargetIlo};
+use crate::cputemp::TempData;
+use crate::pid::PidController;
 
 /// Generates fan control commands for a specific target ILO configuration
 ///
+/// Emits `fan p N max X` for every target fan, followed by `fan p N min Y`
+/// with the `min_fan_speed` of the matching band, or the iLO's default floor
+/// when the band sets none.
+///
 /// # Arguments
 ///
 /// * `target` - Target ILO configuration
@@ -14,7 +27,7 @@ use crate::config::{TargetFans, TargetIlo};
 /// # Example
 ///
 /// ```
-/// use ilo4_fan_control::config::{FanConfig, TargetFans, TargetIlo};
+/// use ilo4_fan_control::config::{ControlMode, FanConfig, TargetFans, TargetIlo};
 /// use ilo4_fan_control::gen_ssh::generate_fan_commands;
 /// let target_ilo = TargetIlo {
 ///     host: String::from("example_host"), // Added host initialization
@@ -22,62 +35,815 @@ use crate::config::{TargetFans, TargetIlo};
 ///     password_base64: String::from("password_base64"),
 ///     
 ///     target_fans: TargetFans::NumFans(4),
+///     sensors: vec![],
+///     control_mode: ControlMode::Step,
+///     hysteresis: 0,
 ///     temperature_fan_config: vec![
 ///         FanConfig {
 ///             min_temp: 0,
 ///             max_temp: 30,
 ///             max_fan_speed: 50,
+///             min_fan_speed: None,
 ///         },
 ///         FanConfig {
 ///             min_temp: 31,
 ///             max_temp: 60,
 ///             max_fan_speed: 75,
+///             min_fan_speed: None,
 ///         },
 ///       
-///       ]
+///       ],
+///     pid: None,
+///     curves: vec![],
+///     fan_groups: vec![],
+///     fan_failure_compensation: 20,
+///     tls: Default::default(),
 /// };
 ///
 /// let commands = generate_fan_commands(&target_ilo, 45);
 /// ```
 pub fn generate_fan_commands(target: &TargetIlo, current_temp: u8) -> Vec<String> {
-    let mut commands = Vec::new();
+    generate_fan_commands_with_state(target, current_temp, &mut FanCurveState::default())
+}
+
+/// Generates fan control commands while applying hysteresis
+///
+/// Behaves like `generate_fan_commands`, but remembers the previous decision
+/// in `state` so that the fan speed is only lowered once the temperature has
+/// dropped `hysteresis` degrees below the band that is currently applied.
+///
+/// # Arguments
+///
+/// * `target` - Target ILO configuration
+/// * `current_temp` - Current temperature reading
+/// * `state` - State of the previous run for the same target
+///
+/// # Returns
+///
+/// * `Vec<String>` - List of fan control commands
+pub fn generate_fan_commands_with_state(
+    target: &TargetIlo,
+    current_temp: u8,
+    state: &mut FanCurveState,
+) -> Vec<String> {
+    // If no fan speed can be derived from the config, return empty commands
+    match calculate_fan_speed_with_state(target, current_temp, state) {
+        Some(speed) => fan_numbers(&target.target_fans)
+            .into_iter()
+            .flat_map(|fan_number| fan_speed_commands(fan_number, speed))
+            .collect(),
+        None => Vec::new(),
+    }
+}
 
-    // Find the appropriate fan config based on current temperature
-    let fan_config = target
-        .temperature_fan_config
+/// Generates fan control commands from the sensors of all fan curves
+///
+/// Every curve of the target reads the highest temperature of its own
+/// sensors and demands a fan speed. Each fan runs at the highest speed
+/// demanded by the curves of the fan groups it belongs to.
+///
+/// # Arguments
+///
+/// * `target` - Target ILO configuration
+/// * `temp_data` - Current sensor readings of the target
+/// * `states` - States of the previous run, one per fan curve
+///
+/// # Returns
+///
+/// * `Result<Vec<String>>` - List of fan control commands, or an error if
+///   the sensors of a curve match no sensor
+pub fn generate_fan_commands_from_sensors(
+    target: &TargetIlo,
+    temp_data: &TempData,
+    states: &mut Vec<FanCurveState>,
+) -> Result<Vec<String>> {
+    Ok(generate_commands_from_speeds(&calculate_fan_speeds(
+        target, temp_data, states,
+    )?))
+}
+
+/// Generates fan control commands for already calculated fan speeds
+///
+/// # Arguments
+///
+/// * `speeds` - Fan speed window by fan number, as returned by
+///   `calculate_fan_speeds`
+///
+/// # Returns
+///
+/// * `Vec<String>` - List of fan control commands
+pub fn generate_commands_from_speeds(speeds: &BTreeMap<u8, FanSpeed>) -> Vec<String> {
+    speeds
         .iter()
-        .find(|config| current_temp >= config.min_temp && current_temp <= config.max_temp);
+        .flat_map(|(&fan_number, &speed)| fan_speed_commands(fan_number, speed))
+        .collect()
+}
+
+/// Fan whose reading does not match the fan speed window sent to the iLO
+#[derive(Debug, Clone, PartialEq)]
+pub struct FanMismatch {
+    /// Fan number as used by `fan p N`
+    pub fan_number: u8,
+    /// Fan name reported by the iLO
+    pub name: String,
+    /// Fan speed percentage reported by the iLO
+    pub current: u8,
+    /// Fan speed window that was sent to the iLO
+    pub expected: FanSpeed,
+}
 
-    // If no matching fan config found, return empty commands
-    let fan_speed = match fan_config {
-        Some(config) => {
-            // Calculate the fan speed threshold (0-255 scale from percentage)
-            ((config.max_fan_speed as f32) * 2.55).round() as u8
+impl fmt::Display for FanMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at {}%, expected at most {}%",
+            self.name, self.current, self.expected.max
+        )?;
+        if let Some(min) = self.expected.min {
+            write!(f, " and at least {}%", min)?;
         }
-        None => return commands,
+        Ok(())
+    }
+}
+
+/// Compares the fan readings of the iLO with the fan speeds sent to it
+///
+/// A fan running faster than its ceiling or slower than its floor by more
+/// than `tolerance` percentage points ignores the commands, which happens
+/// when the iLO firmware does not accept `fan` commands. Fans reported as
+/// failed and empty fan bays are left out, as they cannot follow any command.
+///
+/// # Arguments
+///
+/// * `speeds` - Fan speed window by fan number that was applied
+/// * `temp_data` - Fan readings taken after the fans had time to settle
+/// * `tolerance` - Allowed deviation in percentage points
+///
+/// # Returns
+///
+/// * `Vec<FanMismatch>` - Fans that do not follow the commands
+pub fn verify_fan_speeds(
+    speeds: &BTreeMap<u8, FanSpeed>,
+    temp_data: &TempData,
+    tolerance: f32,
+) -> Vec<FanMismatch> {
+    let failed_fans = temp_data.failed_fans();
+
+    temp_data
+        .fans
+        .iter()
+        .enumerate()
+        .filter(|(_, fan)| fan.status != "Absent" && !failed_fans.contains(fan))
+        .filter_map(|(index, fan)| {
+            // "Fan 1" is addressed as `fan p 0`
+            let fan_number = fan
+                .name
+                .trim_start_matches("Fan ")
+                .parse::<u8>()
+                .ok()
+                .and_then(|number| number.checked_sub(1))
+                .unwrap_or(index as u8);
+            let expected = *speeds.get(&fan_number)?;
+
+            let current = fan.current as f32;
+            let too_fast = current > expected.max + tolerance;
+            let too_slow = expected.min.is_some_and(|min| current < min - tolerance);
+            (too_fast || too_slow).then(|| FanMismatch {
+                fan_number,
+                name: fan.name.clone(),
+                current: fan.current,
+                expected,
+            })
+        })
+        .collect()
+}
+
+/// Generates the commands that apply a fixed fan speed window to fans
+///
+/// # Arguments
+///
+/// * `target_fans` - Fans to change
+/// * `fan_speed` - Fan speed ceiling and optional floor to apply
+///
+/// # Returns
+///
+/// * `Vec<String>` - List of fan control commands
+pub fn generate_manual_commands(target_fans: &TargetFans, fan_speed: FanSpeed) -> Vec<String> {
+    fan_numbers(target_fans)
+        .into_iter()
+        .flat_map(|fan_number| fan_speed_commands(fan_number, fan_speed))
+        .collect()
+}
+
+/// Generates the commands that lift the fan caps of a target
+///
+/// Sets the ceiling of every fan used by any fan group of the target to
+/// `fan_speed` and returns its floor to the iLO's default floor, so neither
+/// a quiet profile nor a raised floor stays in effect while the daemon is
+/// not running.
+///
+/// # Arguments
+///
+/// * `target` - Target ILO configuration
+/// * `fan_speed` - Fan speed percentage (0-100) to apply
+///
+/// # Returns
+///
+/// * `Vec<String>` - List of fan control commands
+pub fn generate_reset_commands(target: &TargetIlo, fan_speed: u8) -> Vec<String> {
+    let fan_speed = FanSpeed {
+        max: fan_speed as f32,
+        min: None,
+    };
+    target_fan_numbers(target)
+        .into_iter()
+        .flat_map(|fan_number| fan_speed_commands(fan_number, fan_speed))
+        .collect()
+}
+
+/// Generates the commands that force every fan of a target to a fixed speed
+///
+/// Sets both the ceiling and the floor of every fan used by any fan group of
+/// the target, so the fans run at `fan_speed` regardless of the iLO's own
+/// fan algorithm.
+///
+/// # Arguments
+///
+/// * `target` - Target ILO configuration
+/// * `fan_speed` - Fan speed percentage (0-100) to apply
+///
+/// # Returns
+///
+/// * `Vec<String>` - List of fan control commands
+pub fn generate_failsafe_commands(target: &TargetIlo, fan_speed: u8) -> Vec<String> {
+    let fan_speed = FanSpeed {
+        max: fan_speed as f32,
+        min: Some(fan_speed as f32),
     };
+    target_fan_numbers(target)
+        .into_iter()
+        .flat_map(|fan_number| fan_speed_commands(fan_number, fan_speed))
+        .collect()
+}
 
-    // Generate commands for each fan
-    match &target.target_fans {
-        TargetFans::NumFans(count) => {
-            for fan_number in 0..=*count - 1 {
-                commands.push(format!("fan p {} max {}", fan_number, fan_speed));
+/// Generates the commands that lower the floors raised by the failsafe
+///
+/// Has to be sent before the regular fan commands once the failsafe ends,
+/// as those leave out the fans of groups whose curves yield no speed.
+/// The floors go back to the iLO's default floor.
+///
+/// # Arguments
+///
+/// * `target` - Target ILO configuration
+///
+/// # Returns
+///
+/// * `Vec<String>` - List of fan control commands
+pub fn generate_failsafe_release_commands(target: &TargetIlo) -> Vec<String> {
+    target_fan_numbers(target)
+        .into_iter()
+        .map(|fan_number| format!("fan p {} min {}", fan_number, ILO_DEFAULT_MIN_PWM))
+        .collect()
+}
+
+/// Fan speed window demanded by a fan curve
+///
+/// Percentages (0-100) applied with `fan p N max` and `fan p N min`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FanSpeed {
+    /// Fan speed ceiling
+    pub max: f32,
+    /// Fan speed floor, or `None` for the iLO's default floor
+    pub min: Option<f32>,
+}
+
+impl FanSpeed {
+    /// Combines two demands, keeping the higher ceiling and the higher floor
+    ///
+    /// The floor is limited to the ceiling so the window never becomes empty.
+    pub fn combine(self, other: FanSpeed) -> FanSpeed {
+        let max = self.max.max(other.max);
+        let min = match (self.min, other.min) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        };
+        FanSpeed {
+            max,
+            min: min.map(|min| min.min(max)),
+        }
+    }
+
+    /// Raises the ceiling and the floor by `points`, up to 100%
+    pub fn raise(self, points: f32) -> FanSpeed {
+        FanSpeed {
+            max: (self.max + points).min(100.0),
+            min: self.min.map(|min| (min + points).min(100.0)),
+        }
+    }
+}
+
+/// Fan speed floor the iLO applies on its own, as PWM value (0-255)
+pub const ILO_DEFAULT_MIN_PWM: u8 = 10;
+
+/// Builds the `fan p N max X` and `fan p N min Y` commands for a single fan
+///
+/// The floor is always sent, so a floor of a previous band never stays in
+/// effect. Without a floor of its own the fan gets the iLO's default floor,
+/// limited to the ceiling.
+fn fan_speed_commands(fan_number: u8, fan_speed: FanSpeed) -> Vec<String> {
+    let max = percent_to_pwm(fan_speed.max);
+    let min = fan_speed
+        .min
+        .map_or(ILO_DEFAULT_MIN_PWM.min(max), percent_to_pwm);
+    vec![
+        format!("fan p {} max {}", fan_number, max),
+        format!("fan p {} min {}", fan_number, min),
+    ]
+}
+
+/// Converts a fan speed percentage to the 0-255 scale used by the iLO
+///
+/// # Example
+///
+/// ```
+/// use ilo4_fan_control::gen_ssh::percent_to_pwm;
+///
+/// assert_eq!(percent_to_pwm(40.0), 102);
+/// assert_eq!(percent_to_pwm(100.0), 255);
+/// ```
+pub fn percent_to_pwm(fan_speed: f32) -> u8 {
+    (fan_speed * 2.55).round() as u8
+}
+
+/// Returns the fan numbers of every fan group of a target without duplicates
+fn target_fan_numbers(target: &TargetIlo) -> BTreeSet<u8> {
+    target
+        .fan_groups()
+        .iter()
+        .flat_map(|group| fan_numbers(&group.target_fans))
+        .collect()
+}
+
+/// Returns the fan numbers used by `fan p N` (starting from 0)
+///
+/// `NumFans` addresses fans from 0, while `TargetFans` lists fans
+/// starting from 1.
+pub fn fan_numbers(target_fans: &TargetFans) -> Vec<u8> {
+    match target_fans {
+        TargetFans::NumFans(count) => (0..*count).collect(),
+        TargetFans::TargetFans(fans) => fans.iter().map(|&fan_number| fan_number - 1).collect(),
+    }
+}
+
+/// Calculates the fan speed percentage for the current temperature
+///
+/// # Arguments
+///
+/// * `target` - Target ILO configuration
+/// * `current_temp` - Current temperature reading
+///
+/// # Returns
+///
+/// * `Option<FanSpeed>` - Fan speed window, or `None` if the configuration
+///   does not cover the temperature
+pub fn calculate_fan_speed(target: &TargetIlo, current_temp: u8) -> Option<FanSpeed> {
+    calculate_fan_speed_with_state(target, current_temp, &mut FanCurveState::default())
+}
+
+/// Calculates the fan speed percentage while applying hysteresis
+///
+/// In `Step` mode the previously applied band is kept until the temperature
+/// falls `hysteresis` degrees below its `min_temp`. In `Linear` mode the
+/// previous temperature is kept until the temperature has fallen by
+/// `hysteresis` degrees. Rising temperatures always take effect immediately.
+/// In `Pid` mode the PID controller kept in `state` is updated with the
+/// time elapsed since the previous call.
+///
+/// # Arguments
+///
+/// * `target` - Target ILO configuration
+/// * `current_temp` - Current temperature reading
+/// * `state` - State of the previous run, updated with the new decision
+///
+/// # Returns
+///
+/// * `Option<FanSpeed>` - Fan speed window, or `None` if the configuration
+///   does not cover the temperature
+pub fn calculate_fan_speed_with_state(
+    target: &TargetIlo,
+    current_temp: u8,
+    state: &mut FanCurveState,
+) -> Option<FanSpeed> {
+    calculate_curve_fan_speed(&target.primary_curve(), current_temp, state)
+}
+
+/// Calculates the fan speed of every fan of a target
+///
+/// Each fan group demands the highest ceiling and floor of its curves. A fan
+/// that belongs to several groups gets the highest demand of those groups.
+/// Groups whose curves yield no speed leave their fans out of the result.
+/// A curve whose sensors match none of the readings is an error, as its
+/// fans would otherwise silently follow the other curves only.
+///
+/// While the iLO reports a failed fan, every fan is raised by the target's
+/// `fan_failure_compensation` so the remaining fans make up for it.
+///
+/// # Arguments
+///
+/// * `target` - Target ILO configuration
+/// * `temp_data` - Current sensor readings of the target
+/// * `states` - States of the previous run, one per fan curve of all
+///   groups; resized to the number of curves of the target
+///
+/// # Returns
+///
+/// * `Result<BTreeMap<u8, FanSpeed>>` - Fan speed window by fan number as
+///   used by `fan p N`, or an error if the sensors of a curve match no sensor
+pub fn calculate_fan_speeds(
+    target: &TargetIlo,
+    temp_data: &TempData,
+    states: &mut Vec<FanCurveState>,
+) -> Result<BTreeMap<u8, FanSpeed>> {
+    let groups = target.fan_groups();
+    let num_curves = groups.iter().map(|group| group.fan_curves().len()).sum();
+    states.resize_with(num_curves, FanCurveState::default);
+
+    let mut speeds = BTreeMap::new();
+    let mut remaining_states = &mut states[..];
+
+    for group in &groups {
+        let curves = group.fan_curves();
+        let (group_states, rest) = remaining_states.split_at_mut(curves.len());
+        remaining_states = rest;
+
+        let mut group_speed: Option<FanSpeed> = None;
+        for (curve, state) in curves.iter().zip(group_states.iter_mut()) {
+            let current_temp = temp_data.max_temperature(&curve.sensors).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "No sensor of {} matches the fan curve sensors {:?}",
+                    target.host,
+                    curve.sensors
+                )
+            })?;
+            if let Some(speed) = calculate_curve_fan_speed(curve, current_temp, state) {
+                group_speed = Some(group_speed.map_or(speed, |group| group.combine(speed)));
             }
         }
-        TargetFans::TargetFans(fans) => {
-            for &fan_number in fans {
-                commands.push(format!("fan p {} max {}", fan_number - 1, fan_speed));
+
+        if let Some(group_speed) = group_speed {
+            for fan_number in fan_numbers(&group.target_fans) {
+                let speed = speeds.entry(fan_number).or_insert(group_speed);
+                *speed = speed.combine(group_speed);
+            }
+        }
+    }
+
+    if target.fan_failure_compensation > 0 && !temp_data.failed_fans().is_empty() {
+        let points = target.fan_failure_compensation as f32;
+        for speed in speeds.values_mut() {
+            *speed = speed.raise(points);
+        }
+    }
+
+    Ok(speeds)
+}
+
+/// Calculates the fan speed percentage of a single fan curve
+///
+/// See `calculate_fan_speed_with_state` for how the state is used.
+///
+/// # Arguments
+///
+/// * `curve` - Fan curve configuration
+/// * `current_temp` - Current temperature of the curve's sensors
+/// * `state` - State of the previous run, updated with the new decision
+///
+/// # Returns
+///
+/// * `Option<FanSpeed>` - Fan speed window, or `None` if the curve does not
+///   cover the temperature
+pub fn calculate_curve_fan_speed(
+    curve: &FanCurve,
+    current_temp: u8,
+    state: &mut FanCurveState,
+) -> Option<FanSpeed> {
+    let fan_configs = &curve.temperature_fan_config;
+
+    match curve.control_mode {
+        ControlMode::Step => {
+            let band = step_band(fan_configs, current_temp, curve.hysteresis, state.last_band);
+            state.last_band = band;
+            band.map(|index| FanSpeed {
+                max: fan_configs[index].max_fan_speed as f32,
+                min: fan_configs[index].min_fan_speed.map(|min| min as f32),
+            })
+        }
+        ControlMode::Linear => {
+            let temp = match state.last_temp {
+                Some(last_temp)
+                    if current_temp < last_temp && last_temp - current_temp < curve.hysteresis =>
+                {
+                    last_temp
+                }
+                _ => current_temp,
+            };
+            state.last_temp = Some(temp);
+            linear_fan_speed(fan_configs, temp)
+        }
+        ControlMode::Pid => {
+            let pid_config = curve.pid.as_ref()?;
+
+            let now = Instant::now();
+            let dt = state
+                .last_update
+                .map(|last_update| now.duration_since(last_update).as_secs_f32())
+                .unwrap_or(0.0);
+            state.last_update = Some(now);
+
+            let controller = state
+                .pid
+                .get_or_insert_with(|| PidController::new(pid_config.clone()));
+            Some(FanSpeed {
+                max: controller.update(current_temp as f32, dt),
+                min: None,
+            })
+        }
+    }
+}
+
+/// Fan commands last applied to a target
+///
+/// Lets the daemon skip the SSH round trip when the fans are already set the
+/// same way, while still sending unchanged commands again from time to time
+/// to restore settings lost by an iLO reboot or changed by hand.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct AppliedCommands {
+    /// Commands applied by the last successful run
+    commands: Vec<String>,
+    /// Time the commands were applied
+    applied_at: Option<Instant>,
+}
+
+impl AppliedCommands {
+    /// Checks whether commands have to be sent to the iLO
+    ///
+    /// # Arguments
+    ///
+    /// * `commands` - Commands the current run would send
+    /// * `now` - Current time
+    /// * `force_refresh` - Time after which unchanged commands are sent again
+    ///
+    /// # Returns
+    ///
+    /// * `bool` - `true` if the commands differ from the applied ones or the
+    ///   refresh is due
+    pub fn needs_sending(
+        &self,
+        commands: &[String],
+        now: Instant,
+        force_refresh: Duration,
+    ) -> bool {
+        let refresh_due = self
+            .applied_at
+            .is_none_or(|applied_at| now.saturating_duration_since(applied_at) >= force_refresh);
+        refresh_due || commands != self.commands.as_slice()
+    }
+
+    /// Records commands that were applied
+    pub fn record(&mut self, commands: Vec<String>, now: Instant) {
+        self.commands = commands;
+        self.applied_at = Some(now);
+    }
+
+    /// Forgets the applied commands, so the next commands are always sent
+    pub fn clear(&mut self) {
+        *self = AppliedCommands::default();
+    }
+
+    /// Returns the commands applied by the last successful run
+    pub fn commands(&self) -> &[String] {
+        &self.commands
+    }
+}
+
+/// Result of checking applied fan speeds against new fan readings
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerificationOutcome {
+    /// The fans follow the applied speeds, or nothing was waiting for a check
+    Verified,
+    /// Fans ignore the commands, which should be sent again
+    Resend(Vec<FanMismatch>),
+    /// Fans still ignore the commands after every retry
+    Failed(Vec<FanMismatch>),
+}
+
+/// Applied fan speeds waiting to be checked against the next fan readings
+///
+/// The check happens on the following run instead of right after the
+/// commands were sent, so the fans get a whole run period to settle and the
+/// control loop never waits for them.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PendingVerification {
+    /// Fan speed window by fan number applied by the last commands
+    speeds: Option<BTreeMap<u8, FanSpeed>>,
+    /// Number of times the commands were sent again because fans ignored them
+    resends: u8,
+}
+
+impl PendingVerification {
+    /// Records fan speeds that were just applied
+    ///
+    /// # Arguments
+    ///
+    /// * `speeds` - Fan speed window by fan number
+    pub fn expect(&mut self, speeds: BTreeMap<u8, FanSpeed>) {
+        self.speeds = Some(speeds);
+    }
+
+    /// Checks the applied fan speeds against new fan readings
+    ///
+    /// # Arguments
+    ///
+    /// * `temp_data` - Fan readings of the current run
+    /// * `tolerance` - Allowed deviation in percentage points
+    /// * `retries` - Number of times the commands may be sent again
+    ///
+    /// # Returns
+    ///
+    /// * `VerificationOutcome` - Whether the fans follow the applied speeds
+    pub fn check(
+        &mut self,
+        temp_data: &TempData,
+        tolerance: f32,
+        retries: u8,
+    ) -> VerificationOutcome {
+        let Some(speeds) = self.speeds.take() else {
+            return VerificationOutcome::Verified;
+        };
+
+        let mismatches = verify_fan_speeds(&speeds, temp_data, tolerance);
+        if mismatches.is_empty() {
+            self.resends = 0;
+            VerificationOutcome::Verified
+        } else if self.resends < retries {
+            self.resends += 1;
+            VerificationOutcome::Resend(mismatches)
+        } else {
+            self.resends = 0;
+            VerificationOutcome::Failed(mismatches)
+        }
+    }
+
+    /// Number of times the commands were sent again so far
+    pub fn resends(&self) -> u8 {
+        self.resends
+    }
+
+    /// Forgets the applied fan speeds, e.g. after the failsafe took over
+    pub fn clear(&mut self) {
+        *self = PendingVerification::default();
+    }
+}
+
+/// Remembers the last fan speed decision of a fan curve
+///
+/// Used by `calculate_curve_fan_speed` to apply hysteresis and PID control
+/// between runs.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FanCurveState {
+    /// Index of the band applied in `Step` mode
+    last_band: Option<usize>,
+    /// Temperature used for the last calculation in `Linear` mode
+    last_temp: Option<u8>,
+    /// Controller used in `Pid` mode
+    pid: Option<PidController>,
+    /// Time of the last update of the PID controller
+    last_update: Option<Instant>,
+}
+
+/// Returns the temperature band of a fan curve that contains a temperature
+///
+/// # Arguments
+///
+/// * `curve` - Fan curve configuration
+/// * `current_temp` - Current temperature of the curve's sensors
+///
+/// # Returns
+///
+/// * `Option<&FanConfig>` - The matching band, or `None` if no band covers
+///   the temperature or the curve uses PID control
+pub fn current_band(curve: &FanCurve, current_temp: u8) -> Option<&FanConfig> {
+    match curve.control_mode {
+        ControlMode::Pid => None,
+        ControlMode::Step | ControlMode::Linear => {
+            let index = step_band(&curve.temperature_fan_config, current_temp, 0, None)?;
+            curve.temperature_fan_config.get(index)
+        }
+    }
+}
+
+/// Selects the band for the temperature, keeping the previous band while the
+/// temperature is still within `hysteresis` degrees below its `min_temp`
+///
+/// Temperatures above every band select the hottest band, so a curve never
+/// stops demanding a speed when it matters most.
+fn step_band(
+    fan_configs: &[FanConfig],
+    current_temp: u8,
+    hysteresis: u8,
+    last_band: Option<usize>,
+) -> Option<usize> {
+    if let Some(index) = last_band {
+        if let Some(config) = fan_configs.get(index) {
+            if current_temp < config.min_temp && config.min_temp - current_temp < hysteresis {
+                return Some(index);
             }
         }
     }
 
-    commands
+    fan_configs
+        .iter()
+        .position(|config| current_temp >= config.min_temp && current_temp <= config.max_temp)
+        .or_else(|| {
+            fan_configs
+                .iter()
+                .enumerate()
+                .max_by_key(|(_, config)| config.max_temp)
+                .filter(|(_, config)| current_temp > config.max_temp)
+                .map(|(index, _)| index)
+        })
+}
+
+/// Interpolates the fan speed between `(min_temp, max_fan_speed)` points
+///
+/// Temperatures below the first point or above the last point are clamped
+/// to the speed of that point, so the curve always yields a speed as long
+/// as at least one band is configured. If any band sets `min_fan_speed`,
+/// the floor is interpolated the same way, treating missing floors as 0.
+fn linear_fan_speed(fan_configs: &[FanConfig], current_temp: u8) -> Option<FanSpeed> {
+    let temp = current_temp as f32;
+
+    let max_points = fan_configs
+        .iter()
+        .map(|config| (config.min_temp as f32, config.max_fan_speed as f32))
+        .collect();
+    let max = interpolate(max_points, temp)?;
+
+    let min = if fan_configs
+        .iter()
+        .any(|config| config.min_fan_speed.is_some())
+    {
+        let min_points = fan_configs
+            .iter()
+            .map(|config| {
+                (
+                    config.min_temp as f32,
+                    config.min_fan_speed.unwrap_or(0) as f32,
+                )
+            })
+            .collect();
+        interpolate(min_points, temp)
+    } else {
+        None
+    };
+
+    Some(FanSpeed { max, min })
+}
+
+/// Linearly interpolates the value at `temp` between `(temperature, value)`
+/// points, clamping to the first and last point
+fn interpolate(mut points: Vec<(f32, f32)>, temp: f32) -> Option<f32> {
+    points.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let first = points.first()?;
+    let last = points.last()?;
+
+    if temp <= first.0 {
+        return Some(first.1);
+    }
+    if temp >= last.0 {
+        return Some(last.1);
+    }
+
+    points
+        .windows(2)
+        .find(|pair| temp >= pair[0].0 && temp <= pair[1].0)
+        .map(|pair| {
+            let (low_temp, low_speed) = pair[0];
+            let (high_temp, high_speed) = pair[1];
+            if high_temp == low_temp {
+                high_speed
+            } else {
+                low_speed + (high_speed - low_speed) * (temp - low_temp) / (high_temp - low_temp)
+            }
+        })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::FanConfig;
+    use crate::config::{FanGroup, PidConfig, SensorSelector};
+    use crate::cputemp::{Fan, Sensor};
     use base64::{engine::general_purpose::STANDARD, Engine as _};
 
     /// テスト用のTargetIloインスタンスを作成する補助関数
@@ -89,23 +855,34 @@ mod tests {
             user: String::from("admin"),
             password_base64: password,
             target_fans: fan_type,
+            sensors: vec![],
+            control_mode: ControlMode::Step,
+            hysteresis: 0,
             temperature_fan_config: vec![
                 FanConfig {
                     min_temp: 0,
                     max_temp: 30,
                     max_fan_speed: 50, // 50% = 128 in 0-255 scale
+                    min_fan_speed: None,
                 },
                 FanConfig {
                     min_temp: 31,
                     max_temp: 60,
                     max_fan_speed: 75, // 75% = 191 in 0-255 scale
+                    min_fan_speed: None,
                 },
                 FanConfig {
                     min_temp: 61,
                     max_temp: 85,
                     max_fan_speed: 100, // 100% = 255 in 0-255 scale
+                    min_fan_speed: None,
                 },
             ],
+            pid: None,
+            curves: vec![],
+            fan_groups: vec![],
+            fan_failure_compensation: 20,
+            tls: Default::default(),
         }
     }
 
@@ -118,26 +895,27 @@ mod tests {
         let low_temp_commands = generate_fan_commands(&target, 25);
         assert_eq!(
             low_temp_commands.len(),
-            3,
-            "3つのファンコマンドが生成されるべき"
+            6,
+            "3つのファンにmaxとminのコマンドが生成されるべき"
         );
         assert_eq!(low_temp_commands[0], "fan p 0 max 128");
-        assert_eq!(low_temp_commands[1], "fan p 1 max 128");
-        assert_eq!(low_temp_commands[2], "fan p 2 max 128");
+        assert_eq!(low_temp_commands[1], "fan p 0 min 10");
+        assert_eq!(low_temp_commands[2], "fan p 1 max 128");
+        assert_eq!(low_temp_commands[4], "fan p 2 max 128");
 
         // 中温域でのテスト (31-60℃)
         let mid_temp_commands = generate_fan_commands(&target, 45);
-        assert_eq!(mid_temp_commands.len(), 3);
+        assert_eq!(mid_temp_commands.len(), 6);
         assert_eq!(mid_temp_commands[0], "fan p 0 max 191");
-        assert_eq!(mid_temp_commands[1], "fan p 1 max 191");
-        assert_eq!(mid_temp_commands[2], "fan p 2 max 191");
+        assert_eq!(mid_temp_commands[2], "fan p 1 max 191");
+        assert_eq!(mid_temp_commands[4], "fan p 2 max 191");
 
         // 高温域でのテスト (61-85℃)
         let high_temp_commands = generate_fan_commands(&target, 70);
-        assert_eq!(high_temp_commands.len(), 3);
+        assert_eq!(high_temp_commands.len(), 6);
         assert_eq!(high_temp_commands[0], "fan p 0 max 255");
-        assert_eq!(high_temp_commands[1], "fan p 1 max 255");
-        assert_eq!(high_temp_commands[2], "fan p 2 max 255");
+        assert_eq!(high_temp_commands[2], "fan p 1 max 255");
+        assert_eq!(high_temp_commands[4], "fan p 2 max 255");
     }
 
     #[test]
@@ -147,10 +925,14 @@ mod tests {
 
         // 中温域でのテスト
         let commands = generate_fan_commands(&target, 45);
-        assert_eq!(commands.len(), 3, "3つのファンコマンドが生成されるべき");
+        assert_eq!(
+            commands.len(),
+            6,
+            "3つのファンにmaxとminのコマンドが生成されるべき"
+        );
         assert_eq!(commands[0], "fan p 0 max 191");
-        assert_eq!(commands[1], "fan p 2 max 191");
-        assert_eq!(commands[2], "fan p 4 max 191");
+        assert_eq!(commands[2], "fan p 2 max 191");
+        assert_eq!(commands[4], "fan p 4 max 191");
     }
 
     #[test]
@@ -159,12 +941,16 @@ mod tests {
         let target = create_test_target(TargetFans::NumFans(2));
         let password: String = STANDARD.encode("password123");
 
-        // 設定された範囲よりも高温
+        // 設定された範囲よりも高温では最も高温の帯域が使われる
         let high_out_commands = generate_fan_commands(&target, 90);
         assert_eq!(
-            high_out_commands.len(),
-            0,
-            "範囲外の温度では空のコマンドリストが返されるべき"
+            high_out_commands,
+            vec![
+                "fan p 0 max 255",
+                "fan p 0 min 10",
+                "fan p 1 max 255",
+                "fan p 1 min 10"
+            ]
         );
 
         // 最小温度と最大温度が厳密に比較されることを確認
@@ -173,18 +959,28 @@ mod tests {
             user: String::from("admin"),
             password_base64: password,
             target_fans: TargetFans::NumFans(1),
+            sensors: vec![],
+            control_mode: ControlMode::Step,
+            hysteresis: 0,
             temperature_fan_config: vec![
                 FanConfig {
                     min_temp: 20,
                     max_temp: 30,
                     max_fan_speed: 50,
+                    min_fan_speed: None,
                 },
                 FanConfig {
                     min_temp: 40,
                     max_temp: 50,
                     max_fan_speed: 75,
+                    min_fan_speed: None,
                 },
             ],
+            pid: None,
+            curves: vec![],
+            fan_groups: vec![],
+            fan_failure_compensation: 20,
+            tls: Default::default(),
         };
 
         // 設定の隙間に当たる温度
@@ -218,17 +1014,802 @@ mod tests {
                 user: String::from("admin"),
                 password_base64: password.clone(),
                 target_fans: TargetFans::NumFans(1),
+                sensors: vec![],
+                control_mode: ControlMode::Step,
+                hysteresis: 0,
                 temperature_fan_config: vec![FanConfig {
                     min_temp: 0,
                     max_temp: 100,
                     max_fan_speed: *percentage,
+                    min_fan_speed: None,
                 }],
+                pid: None,
+                curves: vec![],
+                fan_groups: vec![],
+                fan_failure_compensation: 20,
+                tls: Default::default(),
             };
 
             let commands = generate_fan_commands(&target, 50); // Use a temperature in the valid range
 
-            assert_eq!(commands.len(), 1);
+            assert_eq!(commands.len(), 2);
             assert_eq!(commands[0], format!("fan p 0 max {}", expected));
+            // 既定の下限は上限を超えない
+            assert_eq!(
+                commands[1],
+                format!("fan p 0 min {}", expected.min(&ILO_DEFAULT_MIN_PWM))
+            );
+        }
+    }
+
+    #[test]
+    fn test_generate_fan_commands_linear() {
+        // 線形補間モードのテスト
+        let mut target = create_test_target(TargetFans::NumFans(1));
+        target.control_mode = ControlMode::Linear;
+
+        // 補間点 (0℃, 50%), (31℃, 75%), (61℃, 100%)
+        assert_eq!(generate_fan_commands(&target, 0)[0], "fan p 0 max 128");
+        assert_eq!(generate_fan_commands(&target, 31)[0], "fan p 0 max 191");
+        assert_eq!(generate_fan_commands(&target, 61)[0], "fan p 0 max 255");
+
+        // 46℃ は 31℃ と 61℃ の中間なので 87.5% = 223
+        assert_eq!(generate_fan_commands(&target, 46)[0], "fan p 0 max 223");
+    }
+
+    #[test]
+    fn test_generate_fan_commands_linear_clamped() {
+        // 補間点の範囲外では端の速度に固定される
+        let mut target = create_test_target(TargetFans::NumFans(1));
+        target.control_mode = ControlMode::Linear;
+        target.temperature_fan_config = vec![
+            FanConfig {
+                min_temp: 40,
+                max_temp: 50,
+                max_fan_speed: 20,
+                min_fan_speed: None,
+            },
+            FanConfig {
+                min_temp: 60,
+                max_temp: 70,
+                max_fan_speed: 60,
+                min_fan_speed: None,
+            },
+        ];
+
+        assert_eq!(generate_fan_commands(&target, 30)[0], "fan p 0 max 51");
+        assert_eq!(generate_fan_commands(&target, 90)[0], "fan p 0 max 153");
+        // 50℃ は 20% と 60% の中間で 40% = 102
+        assert_eq!(generate_fan_commands(&target, 50)[0], "fan p 0 max 102");
+    }
+
+    #[test]
+    fn test_calculate_fan_speed_linear_unsorted() {
+        // 設定の順序に関係なく補間される
+        let mut target = create_test_target(TargetFans::NumFans(1));
+        target.control_mode = ControlMode::Linear;
+        target.temperature_fan_config.reverse();
+
+        assert_eq!(
+            calculate_fan_speed(&target, 16).map(|speed| speed.max.round()),
+            Some(63.0)
+        );
+
+        target.temperature_fan_config.clear();
+        assert_eq!(calculate_fan_speed(&target, 16), None);
+    }
+
+    #[test]
+    fn test_step_hysteresis() {
+        // ヒステリシス付きの段階制御
+        let mut target = create_test_target(TargetFans::NumFans(1));
+        target.hysteresis = 3;
+        let mut state = FanCurveState::default();
+
+        // 31℃ で中温域に入る
+        let commands = generate_fan_commands_with_state(&target, 31, &mut state);
+        assert_eq!(commands[0], "fan p 0 max 191");
+
+        // min_temp から 3℃ 未満の低下では中温域を維持する
+        let commands = generate_fan_commands_with_state(&target, 30, &mut state);
+        assert_eq!(commands[0], "fan p 0 max 191");
+        let commands = generate_fan_commands_with_state(&target, 29, &mut state);
+        assert_eq!(commands[0], "fan p 0 max 191");
+
+        // 3℃ 低下したら低温域に下がる
+        let commands = generate_fan_commands_with_state(&target, 28, &mut state);
+        assert_eq!(commands[0], "fan p 0 max 128");
+
+        // 上昇は即座に反映される
+        let commands = generate_fan_commands_with_state(&target, 31, &mut state);
+        assert_eq!(commands[0], "fan p 0 max 191");
+    }
+
+    #[test]
+    fn test_step_without_hysteresis() {
+        // ヒステリシス 0 では状態があっても即座に切り替わる
+        let target = create_test_target(TargetFans::NumFans(1));
+        let mut state = FanCurveState::default();
+
+        let commands = generate_fan_commands_with_state(&target, 31, &mut state);
+        assert_eq!(commands[0], "fan p 0 max 191");
+        let commands = generate_fan_commands_with_state(&target, 30, &mut state);
+        assert_eq!(commands[0], "fan p 0 max 128");
+    }
+
+    #[test]
+    fn test_linear_hysteresis() {
+        // 線形補間モードでのヒステリシス
+        let mut target = create_test_target(TargetFans::NumFans(1));
+        target.control_mode = ControlMode::Linear;
+        target.hysteresis = 2;
+        let mut state = FanCurveState::default();
+
+        let commands = generate_fan_commands_with_state(&target, 46, &mut state);
+        assert_eq!(commands[0], "fan p 0 max 223");
+
+        // 1℃ の低下は無視される
+        let commands = generate_fan_commands_with_state(&target, 45, &mut state);
+        assert_eq!(commands[0], "fan p 0 max 223");
+
+        // 2℃ 低下したら反映される
+        let commands = generate_fan_commands_with_state(&target, 44, &mut state);
+        assert_eq!(commands[0], "fan p 0 max 219");
+    }
+
+    #[test]
+    fn test_pid_mode() {
+        // PID 制御モードのテスト
+        let mut target = create_test_target(TargetFans::NumFans(1));
+        target.control_mode = ControlMode::Pid;
+        target.pid = Some(PidConfig {
+            target_temp: 60,
+            kp: 4.0,
+            ki: 0.0,
+            kd: 0.0,
+            min_fan_speed: 10,
+            max_fan_speed: 90,
+        });
+        let mut state = FanCurveState::default();
+
+        // 目標温度より 5℃ 高い場合は 20% = 51
+        let commands = generate_fan_commands_with_state(&target, 65, &mut state);
+        assert_eq!(commands[0], "fan p 0 max 51");
+
+        // 出力は min_fan_speed と max_fan_speed に制限される
+        let commands = generate_fan_commands_with_state(&target, 40, &mut state);
+        assert_eq!(commands[0], "fan p 0 max 26");
+        let commands = generate_fan_commands_with_state(&target, 95, &mut state);
+        assert_eq!(commands[0], "fan p 0 max 230");
+    }
+
+    #[test]
+    fn test_pid_mode_without_config() {
+        // PID 設定がない場合はコマンドを生成しない
+        let mut target = create_test_target(TargetFans::NumFans(1));
+        target.control_mode = ControlMode::Pid;
+
+        assert_eq!(generate_fan_commands(&target, 65).len(), 0);
+    }
+
+    fn create_temp_data(cpu_temp: u8, pci_temp: u8) -> TempData {
+        let sensor = |name: &str, physical_context: &str, current: u8| Sensor {
+            name: name.to_string(),
+            physical_context: physical_context.to_string(),
+            current: Some(current),
+            health: "OK".to_string(),
+            ..Default::default()
+        };
+
+        TempData {
+            cpu_temps: vec![],
+            high_temp_critical_reached_component: false,
+            high_temp_component_name: vec![],
+            num_fans: 0,
+            fans: vec![],
+            sensors: vec![
+                sensor("02-CPU 1", "CPU", cpu_temp),
+                sensor("03-CPU 2", "CPU", cpu_temp.saturating_sub(2)),
+                sensor("31-PCI 1 Zone", "SystemBoard", pci_temp),
+            ],
         }
     }
+
+    #[test]
+    fn test_generate_fan_commands_from_sensors() {
+        // CPU 用の曲線と PCI 用の曲線の最大値が使われる
+        let mut target = create_test_target(TargetFans::NumFans(2));
+        target.curves = vec![FanCurve {
+            sensors: vec![SensorSelector::Name("PCI 1 Zone".to_string())],
+            control_mode: ControlMode::Step,
+            hysteresis: 0,
+            temperature_fan_config: vec![
+                FanConfig {
+                    min_temp: 0,
+                    max_temp: 60,
+                    max_fan_speed: 10,
+                    min_fan_speed: None,
+                },
+                FanConfig {
+                    min_temp: 61,
+                    max_temp: 100,
+                    max_fan_speed: 90,
+                    min_fan_speed: None,
+                },
+            ],
+            pid: None,
+        }];
+        let mut states = Vec::new();
+
+        // CPU が支配的 (CPU 25℃ → 50%, PCI 40℃ → 10%)
+        let commands =
+            generate_fan_commands_from_sensors(&target, &create_temp_data(25, 40), &mut states)
+                .unwrap();
+        assert_eq!(
+            commands,
+            vec![
+                "fan p 0 max 128",
+                "fan p 0 min 10",
+                "fan p 1 max 128",
+                "fan p 1 min 10"
+            ]
+        );
+        assert_eq!(states.len(), 2);
+
+        // PCI が支配的 (CPU 25℃ → 50%, PCI 65℃ → 90%)
+        let commands =
+            generate_fan_commands_from_sensors(&target, &create_temp_data(25, 65), &mut states)
+                .unwrap();
+        assert_eq!(
+            commands,
+            vec![
+                "fan p 0 max 230",
+                "fan p 0 min 10",
+                "fan p 1 max 230",
+                "fan p 1 min 10"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_step_above_table() {
+        // 最も高温の帯域 (61-85℃) を超えても最大速度を維持する
+        let mut target = create_test_target(TargetFans::NumFans(1));
+        target.temperature_fan_config.reverse();
+        assert_eq!(
+            generate_fan_commands(&target, 86),
+            vec!["fan p 0 max 255", "fan p 0 min 10"]
+        );
+
+        // 静かな PCI 用の曲線が CPU 用の曲線に勝つことはない
+        target.curves = vec![FanCurve {
+            sensors: vec![SensorSelector::Name("PCI 1 Zone".to_string())],
+            control_mode: ControlMode::Step,
+            hysteresis: 0,
+            temperature_fan_config: vec![FanConfig {
+                min_temp: 0,
+                max_temp: 60,
+                max_fan_speed: 10,
+                min_fan_speed: None,
+            }],
+            pid: None,
+        }];
+        let speeds =
+            calculate_fan_speeds(&target, &create_temp_data(86, 40), &mut Vec::new()).unwrap();
+        assert_eq!(speeds[&0].max, 100.0);
+    }
+
+    #[test]
+    fn test_fan_failure_compensation() {
+        // 故障したファンがあると残りのファンの速度を引き上げる
+        let target = create_test_target(TargetFans::NumFans(2));
+        let fan = |name: &str, current: u8, status: &str| Fan {
+            name: name.to_string(),
+            current,
+            status: status.to_string(),
+        };
+        let mut temp_data = create_temp_data(25, 40);
+        temp_data.fans = vec![fan("Fan 1", 30, "OK"), fan("Fan 2", 0, "OK")];
+        let mut states = Vec::new();
+
+        // CPU 25℃ → 50% + 20%
+        let speeds = calculate_fan_speeds(&target, &temp_data, &mut states).unwrap();
+        assert_eq!(speeds[&0].max, 70.0);
+        assert_eq!(speeds[&1].max, 70.0);
+
+        // 上限は 100% を超えない
+        let speed = FanSpeed {
+            max: 90.0,
+            min: Some(85.0),
+        };
+        assert_eq!(
+            speed.raise(20.0),
+            FanSpeed {
+                max: 100.0,
+                min: Some(100.0)
+            }
+        );
+
+        // 補償が無効なら速度は変わらない
+        let mut target = target;
+        target.fan_failure_compensation = 0;
+        let speeds = calculate_fan_speeds(&target, &temp_data, &mut states).unwrap();
+        assert_eq!(speeds[&0].max, 50.0);
+    }
+
+    #[test]
+    fn test_verify_fan_speeds() {
+        // 上限を超える、または下限を下回るファンを検出する
+        let fan = |name: &str, current: u8| Fan {
+            name: name.to_string(),
+            current,
+            status: "OK".to_string(),
+        };
+        let mut temp_data = create_temp_data(25, 40);
+        temp_data.fans = vec![
+            fan("Fan 1", 52),
+            fan("Fan 2", 80),
+            fan("Fan 3", 10),
+            fan("Fan 4", 90),
+            Fan {
+                name: "Fan 5".to_string(),
+                current: 0,
+                status: "Absent".to_string(),
+            },
+        ];
+        let speeds = BTreeMap::from([
+            (
+                0,
+                FanSpeed {
+                    max: 50.0,
+                    min: None,
+                },
+            ),
+            (
+                1,
+                FanSpeed {
+                    max: 50.0,
+                    min: None,
+                },
+            ),
+            (
+                2,
+                FanSpeed {
+                    max: 50.0,
+                    min: Some(30.0),
+                },
+            ),
+            (
+                4,
+                FanSpeed {
+                    max: 50.0,
+                    min: Some(30.0),
+                },
+            ),
+        ]);
+
+        let mismatches = verify_fan_speeds(&speeds, &temp_data, 5.0);
+        let fan_numbers: Vec<u8> = mismatches.iter().map(|m| m.fan_number).collect();
+        // Fan 1 は許容範囲内、Fan 4 は制御対象外、Fan 5 は空のベイ
+        assert_eq!(fan_numbers, vec![1, 2]);
+        assert_eq!(
+            mismatches[1].to_string(),
+            "Fan 3 at 10%, expected at most 50% and at least 30%"
+        );
+
+        // 生成されるコマンドは速度から作られる
+        assert_eq!(
+            generate_commands_from_speeds(&speeds)[..4],
+            [
+                "fan p 0 max 128",
+                "fan p 0 min 10",
+                "fan p 1 max 128",
+                "fan p 1 min 10"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_calculate_fan_speeds_without_match() {
+        // センサーに一致しない曲線はエラーになる
+        let mut target = create_test_target(TargetFans::NumFans(1));
+        target.sensors = vec![SensorSelector::Name("GPU".to_string())];
+        let mut states = Vec::new();
+
+        let error = calculate_fan_speeds(&target, &create_temp_data(25, 40), &mut states)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("example.host.com"));
+        assert!(error.contains("GPU"));
+
+        // 追加の曲線が一致しない場合も同様
+        let mut target = create_test_target(TargetFans::NumFans(1));
+        target.curves = vec![target.primary_curve()];
+        target.curves[0].sensors = vec![SensorSelector::PhysicalContext("GPU".to_string())];
+        assert!(calculate_fan_speeds(&target, &create_temp_data(25, 40), &mut states).is_err());
+
+        // 温度範囲の隙間では速度を決められず、ファンは対象外になる
+        let mut target = create_test_target(TargetFans::NumFans(1));
+        target.temperature_fan_config.remove(0);
+        assert!(
+            calculate_fan_speeds(&target, &create_temp_data(25, 40), &mut states)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_calculate_fan_speeds_fan_groups() {
+        // CPU 上のファン (1-4) と PCIe ライザー上のファン (4-6) を別々に制御する
+        let mut target = create_test_target(TargetFans::TargetFans(vec![1, 2, 3, 4]));
+        target.fan_groups = vec![FanGroup {
+            target_fans: TargetFans::TargetFans(vec![4, 5, 6]),
+            curve: FanCurve {
+                sensors: vec![SensorSelector::Name("PCI 1 Zone".to_string())],
+                control_mode: ControlMode::Step,
+                hysteresis: 0,
+                temperature_fan_config: vec![FanConfig {
+                    min_temp: 0,
+                    max_temp: 100,
+                    max_fan_speed: 60,
+                    min_fan_speed: None,
+                }],
+                pid: None,
+            },
+            curves: vec![],
+        }];
+        let mut states = Vec::new();
+
+        // CPU 25℃ → 50%, PCI → 60%
+        let speeds = calculate_fan_speeds(&target, &create_temp_data(25, 40), &mut states).unwrap();
+        assert_eq!(
+            speeds
+                .into_iter()
+                .map(|(fan_number, speed)| (fan_number, speed.max))
+                .collect::<Vec<_>>(),
+            vec![
+                (0, 50.0),
+                (1, 50.0),
+                (2, 50.0),
+                (3, 60.0),
+                (4, 60.0),
+                (5, 60.0)
+            ]
+        );
+        assert_eq!(states.len(), 2);
+
+        // 両方のグループに属するファン 4 は高い方の速度になる (CPU 70℃ → 100%)
+        let commands =
+            generate_fan_commands_from_sensors(&target, &create_temp_data(70, 40), &mut states)
+                .unwrap();
+        assert_eq!(
+            commands,
+            vec![
+                "fan p 0 max 255",
+                "fan p 0 min 10",
+                "fan p 1 max 255",
+                "fan p 1 min 10",
+                "fan p 2 max 255",
+                "fan p 2 min 10",
+                "fan p 3 max 255",
+                "fan p 3 min 10",
+                "fan p 4 max 153",
+                "fan p 4 min 10",
+                "fan p 5 max 153",
+                "fan p 5 min 10"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_generate_fan_commands_min_fan_speed() {
+        // 下限が設定された帯域では min コマンドも生成される
+        let mut target = create_test_target(TargetFans::TargetFans(vec![1, 2]));
+        target.temperature_fan_config[1].min_fan_speed = Some(20); // 20% = 51
+
+        let commands = generate_fan_commands(&target, 45);
+        assert_eq!(
+            commands,
+            vec![
+                "fan p 0 max 191",
+                "fan p 0 min 51",
+                "fan p 1 max 191",
+                "fan p 1 min 51"
+            ]
+        );
+
+        // 下限のない帯域に移ると iLO の既定の下限に戻す
+        let commands = generate_fan_commands(&target, 25);
+        assert_eq!(
+            commands,
+            vec![
+                "fan p 0 max 128",
+                "fan p 0 min 10",
+                "fan p 1 max 128",
+                "fan p 1 min 10"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_band_transition_resets_floor() {
+        // 上限より高い古い下限が iLO に残らない
+        let mut target = create_test_target(TargetFans::NumFans(1));
+        target.temperature_fan_config[2].min_fan_speed = Some(80); // 80% = 204
+        let mut state = FanCurveState::default();
+
+        let commands = generate_fan_commands_with_state(&target, 70, &mut state);
+        assert_eq!(commands, vec!["fan p 0 max 255", "fan p 0 min 204"]);
+
+        let commands = generate_fan_commands_with_state(&target, 25, &mut state);
+        assert_eq!(commands, vec!["fan p 0 max 128", "fan p 0 min 10"]);
+
+        // 既定の下限も上限を超えない
+        target.temperature_fan_config[0].max_fan_speed = 2;
+        let commands = generate_fan_commands_with_state(&target, 25, &mut state);
+        assert_eq!(commands, vec!["fan p 0 max 5", "fan p 0 min 5"]);
+    }
+
+    #[test]
+    fn test_linear_min_fan_speed() {
+        // 線形補間モードでは下限も補間される
+        let mut target = create_test_target(TargetFans::NumFans(1));
+        target.control_mode = ControlMode::Linear;
+        target.temperature_fan_config[1].min_fan_speed = Some(20);
+        target.temperature_fan_config[2].min_fan_speed = Some(40);
+
+        // 46℃ は 31℃ と 61℃ の中間なので下限は 30% = 77
+        let commands = generate_fan_commands(&target, 46);
+        assert_eq!(commands, vec!["fan p 0 max 223", "fan p 0 min 77"]);
+
+        // 下限のない点は 0% として扱う
+        let commands = generate_fan_commands(&target, 0);
+        assert_eq!(commands, vec!["fan p 0 max 128", "fan p 0 min 0"]);
+    }
+
+    #[test]
+    fn test_fan_speed_combine() {
+        let quiet = FanSpeed {
+            max: 30.0,
+            min: Some(20.0),
+        };
+        let loud = FanSpeed {
+            max: 80.0,
+            min: None,
+        };
+        let floor = FanSpeed {
+            max: 40.0,
+            min: Some(35.0),
+        };
+
+        assert_eq!(
+            quiet.combine(loud),
+            FanSpeed {
+                max: 80.0,
+                min: Some(20.0)
+            }
+        );
+        assert_eq!(
+            quiet.combine(floor),
+            FanSpeed {
+                max: 40.0,
+                min: Some(35.0)
+            }
+        );
+        // 下限は上限を超えない
+        assert_eq!(
+            FanSpeed {
+                max: 30.0,
+                min: None
+            }
+            .combine(FanSpeed {
+                max: 20.0,
+                min: Some(50.0)
+            }),
+            FanSpeed {
+                max: 30.0,
+                min: Some(30.0)
+            }
+        );
+    }
+
+    #[test]
+    fn test_generate_reset_commands() {
+        // すべてのグループのファンが重複なく対象になる
+        let mut target = create_test_target(TargetFans::NumFans(2));
+        target.fan_groups = vec![FanGroup {
+            target_fans: TargetFans::TargetFans(vec![2, 4]),
+            curve: target.primary_curve(),
+            curves: vec![],
+        }];
+
+        assert_eq!(
+            generate_reset_commands(&target, 100),
+            vec![
+                "fan p 0 max 255",
+                "fan p 0 min 10",
+                "fan p 1 max 255",
+                "fan p 1 min 10",
+                "fan p 3 max 255",
+                "fan p 3 min 10"
+            ]
+        );
+
+        // 帯域やフェイルセーフで上げた下限も既定の下限に戻す
+        let mut target = create_test_target(TargetFans::NumFans(1));
+        target.temperature_fan_config[2].min_fan_speed = Some(80);
+        assert_eq!(generate_fan_commands(&target, 70)[1], "fan p 0 min 204");
+        assert_eq!(
+            generate_failsafe_commands(&target, 100)[1],
+            "fan p 0 min 255"
+        );
+        assert_eq!(
+            generate_reset_commands(&target, 100),
+            vec!["fan p 0 max 255", "fan p 0 min 10"]
+        );
+    }
+
+    #[test]
+    fn test_applied_commands() {
+        let commands = vec!["fan p 0 max 128".to_string(), "fan p 0 min 10".to_string()];
+        let force_refresh = Duration::from_secs(600);
+        let now = Instant::now();
+        let mut applied = AppliedCommands::default();
+
+        // 初回は常に送信する
+        assert!(applied.needs_sending(&commands, now, force_refresh));
+        applied.record(commands.clone(), now);
+        assert_eq!(applied.commands(), commands.as_slice());
+
+        // 変化がなければ送信しない
+        let later = now + Duration::from_secs(599);
+        assert!(!applied.needs_sending(&commands, later, force_refresh));
+
+        // 変化があれば送信する
+        let changed = vec!["fan p 0 max 191".to_string(), "fan p 0 min 10".to_string()];
+        assert!(applied.needs_sending(&changed, later, force_refresh));
+
+        // 一定時間が過ぎたら変化がなくても送信する
+        let later = now + force_refresh;
+        assert!(applied.needs_sending(&commands, later, force_refresh));
+
+        // 0 では毎回送信する
+        assert!(applied.needs_sending(&commands, now, Duration::ZERO));
+
+        // 消去後は再び送信する
+        applied.clear();
+        assert!(applied.needs_sending(&commands, now, force_refresh));
+    }
+
+    #[test]
+    fn test_pending_verification() {
+        // 次の読み取りで適用済みの速度を確認する
+        let mut temp_data = create_temp_data(25, 40);
+        temp_data.fans = vec![Fan {
+            name: "Fan 1".to_string(),
+            current: 80,
+            status: "OK".to_string(),
+        }];
+        let speeds = BTreeMap::from([(
+            0,
+            FanSpeed {
+                max: 50.0,
+                min: None,
+            },
+        )]);
+        let mut pending = PendingVerification::default();
+
+        // 確認待ちがなければ何もしない
+        assert_eq!(
+            pending.check(&temp_data, 5.0, 1),
+            VerificationOutcome::Verified
+        );
+
+        // 従わないファンは再送し、再送回数を超えると失敗する
+        pending.expect(speeds.clone());
+        assert!(matches!(
+            pending.check(&temp_data, 5.0, 1),
+            VerificationOutcome::Resend(mismatches) if mismatches.len() == 1
+        ));
+        assert_eq!(pending.resends(), 1);
+        pending.expect(speeds.clone());
+        assert!(matches!(
+            pending.check(&temp_data, 5.0, 1),
+            VerificationOutcome::Failed(_)
+        ));
+        assert_eq!(pending.resends(), 0);
+
+        // 確認は一度だけ行う
+        assert_eq!(
+            pending.check(&temp_data, 5.0, 1),
+            VerificationOutcome::Verified
+        );
+
+        // 従えば再送回数がリセットされる
+        pending.expect(speeds.clone());
+        assert!(matches!(
+            pending.check(&temp_data, 5.0, 1),
+            VerificationOutcome::Resend(_)
+        ));
+        temp_data.fans[0].current = 50;
+        pending.expect(speeds.clone());
+        assert_eq!(
+            pending.check(&temp_data, 5.0, 1),
+            VerificationOutcome::Verified
+        );
+        assert_eq!(pending.resends(), 0);
+
+        pending.expect(speeds);
+        pending.clear();
+        assert_eq!(pending, PendingVerification::default());
+    }
+
+    #[test]
+    fn test_current_band() {
+        // 温度を含む帯を返し、PID では帯がない
+        let mut curve = create_test_target(TargetFans::NumFans(1)).primary_curve();
+        assert_eq!(
+            current_band(&curve, 45).map(|band| band.max_fan_speed),
+            Some(75)
+        );
+        assert_eq!(
+            current_band(&curve, 101).map(|band| band.max_fan_speed),
+            Some(100)
+        );
+
+        curve.control_mode = ControlMode::Pid;
+        assert_eq!(current_band(&curve, 45), None);
+    }
+
+    #[test]
+    fn test_generate_manual_commands() {
+        // 指定したファン (1 始まり) だけを変更する
+        let fan_speed = FanSpeed {
+            max: 40.0,
+            min: Some(20.0),
+        };
+        assert_eq!(
+            generate_manual_commands(&TargetFans::TargetFans(vec![1, 3]), fan_speed),
+            vec![
+                "fan p 0 max 102",
+                "fan p 0 min 51",
+                "fan p 2 max 102",
+                "fan p 2 min 51"
+            ]
+        );
+
+        let fan_speed = FanSpeed {
+            max: 40.0,
+            min: None,
+        };
+        assert_eq!(
+            generate_manual_commands(&TargetFans::NumFans(2), fan_speed),
+            vec![
+                "fan p 0 max 102",
+                "fan p 0 min 10",
+                "fan p 1 max 102",
+                "fan p 1 min 10"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_generate_failsafe_commands() {
+        // 上限と下限の両方を緊急速度に固定する
+        let target = create_test_target(TargetFans::TargetFans(vec![1, 3]));
+
+        assert_eq!(
+            generate_failsafe_commands(&target, 80),
+            vec![
+                "fan p 0 max 204",
+                "fan p 0 min 204",
+                "fan p 2 max 204",
+                "fan p 2 min 204"
+            ]
+        );
+        assert_eq!(
+            generate_failsafe_release_commands(&target),
+            vec!["fan p 0 min 10", "fan p 2 min 10"]
+        );
+    }
 }
@@ -6,11 +6,25 @@
 ///
 /// # Modules
 ///
+/// * `backoff` - Retry schedule of failing targets
 /// * `config` - Configuration structures and parsing
 /// * `cputemp` - CPU temperature monitoring
+/// * `failsafe` - Trust in the readings of a target and the failsafe state
+/// * `fan_output` - Parsing of the iLO's answers to `fan` commands
 /// * `ssh` - SSH connection management
 /// * `gen_ssh` - SSH key generation and command generation
+/// * `pid` - PID controller for closed-loop fan control
+/// * `redfish` - Redfish sessions on the iLO
+/// * `status` - Status views of the targets
+/// * `thermal` - Typed model of the Redfish Thermal resource
+pub mod backoff;
 pub mod config;
 pub mod cputemp;
+pub mod failsafe;
+pub mod fan_output;
 pub mod gen_ssh;
+pub mod pid;
+pub mod redfish;
 pub mod ssh;
+pub mod status;
+pub mod thermal;
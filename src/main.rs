@@ -3,11 +3,13 @@ use clap::{Parser, Subcommand};
 use log::{error, info};
 use std::process;
 
+use ilo4_fan_control::{
+    backoff, config, cputemp, failsafe, fan_output, gen_ssh, redfish, ssh, status,
+};
+
 mod cmds;
-mod config;
-mod cputemp;
-mod gen_ssh;
-mod ssh;
+
+use cmds::status::OutputFormat;
 
 /// HPE iLO4 Fan Control Utility
 ///
@@ -42,7 +44,35 @@ struct Cli {
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// Displays the current fan status
-    Status,
+    Status {
+        /// Output format, every format but text is printed to stdout
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+
+        /// Refresh the readings every given number of seconds until interrupted
+        #[arg(short, long, value_name = "SECS", conflicts_with = "format", value_parser = clap::value_parser!(u64).range(1..))]
+        watch: Option<u64>,
+
+        /// Show every target of a configuration file instead of a single host
+        #[arg(short, long, value_name = "PATH", conflicts_with = "watch")]
+        config: Option<String>,
+
+        /// PEM file with CA certificates to trust for the iLO's certificate
+        #[arg(long, value_name = "PATH", conflicts_with = "config")]
+        ca_bundle: Option<String>,
+
+        /// Accept only the iLO certificate with this SHA-256 fingerprint
+        #[arg(long, value_name = "SHA256", conflicts_with_all = ["config", "ca_bundle"])]
+        fingerprint: Option<String>,
+
+        /// Pin the iLO certificate seen first in this file and accept only it afterwards
+        #[arg(long, value_name = "PATH", conflicts_with_all = ["config", "ca_bundle", "fingerprint"])]
+        pin_file: Option<String>,
+
+        /// Accept any certificate of the iLO without verification
+        #[arg(long, conflicts_with_all = ["config", "ca_bundle", "fingerprint", "pin_file"])]
+        insecure: bool,
+    },
 
     /// Generates sample configuration files and Validate the configuration
     Config {
@@ -69,6 +99,44 @@ enum Commands {
         #[arg(short, long)]
         path: String,
     },
+
+    /// Shows and changes the fan settings of the iLO
+    Fan {
+        #[command(subcommand)]
+        command: FanCommands,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum FanCommands {
+    /// Shows the fan speeds, limits and PID groupings in effect on the iLO
+    Info {
+        /// Print the fan settings as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Sets the fan speed limits of some fans once
+    Set {
+        /// Comma separated fan numbers, starting from 1
+        #[arg(long, value_delimiter = ',', required = true, value_parser = clap::value_parser!(u8).range(1..))]
+        fans: Vec<u8>,
+
+        /// Maximum fan speed percentage (0-100)
+        #[arg(long, value_parser = clap::value_parser!(u8).range(0..=100))]
+        max: u8,
+
+        /// Minimum fan speed percentage (0-100), the iLO's default floor when omitted
+        #[arg(long, value_parser = clap::value_parser!(u8).range(0..=100))]
+        min: Option<u8>,
+    },
+
+    /// Returns every fan to the iLO's own fan control
+    Reset {
+        /// Number of fans to reset when the output of `fan info` cannot be read
+        #[arg(long, value_parser = clap::value_parser!(u8).range(1..))]
+        num_fans: Option<u8>,
+    },
 }
 
 /// Main entry point for the fan control application
@@ -85,9 +153,30 @@ fn main() -> Result<()> {
     info!("Fan Control Utility for HPE iLO4\n");
 
     match &cli.command {
-        Commands::Status => {
-            cmds::status::show_status(cli.host.clone(), cli.user.clone(), cli.password.clone());
-        }
+        Commands::Status {
+            format,
+            watch,
+            config,
+            ca_bundle,
+            fingerprint,
+            pin_file,
+            insecure,
+        } => match config {
+            Some(config_path) => cmds::status::show_config_status(config_path.clone(), *format),
+            None => cmds::status::show_status(
+                cli.host.clone(),
+                cli.user.clone(),
+                cli.password.clone(),
+                cmds::status::tls_config(
+                    ca_bundle.clone(),
+                    fingerprint.clone(),
+                    pin_file.clone(),
+                    *insecure,
+                ),
+                *format,
+                *watch,
+            ),
+        },
         Commands::Config {
             path,
             sample,
@@ -130,6 +219,44 @@ fn main() -> Result<()> {
                 }
             }
         }
+
+        Commands::Fan { command } => match command {
+            FanCommands::Info { json } => {
+                if let Err(e) = cmds::fan::show_fan_info(
+                    cli.host.clone(),
+                    cli.user.clone(),
+                    cli.password.clone(),
+                    *json,
+                ) {
+                    error!("Failed to get fan info: {}", e);
+                    process::exit(1);
+                }
+            }
+            FanCommands::Set { fans, max, min } => {
+                if let Err(e) = cmds::fan::set_fan_speed(
+                    cli.host.clone(),
+                    cli.user.clone(),
+                    cli.password.clone(),
+                    fans.clone(),
+                    *max,
+                    *min,
+                ) {
+                    error!("Failed to set fan speed: {}", e);
+                    process::exit(1);
+                }
+            }
+            FanCommands::Reset { num_fans } => {
+                if let Err(e) = cmds::fan::reset_fans(
+                    cli.host.clone(),
+                    cli.user.clone(),
+                    cli.password.clone(),
+                    *num_fans,
+                ) {
+                    error!("Failed to reset fans: {}", e);
+                    process::exit(1);
+                }
+            }
+        },
     }
 
     Ok(())
@@ -0,0 +1,215 @@
+use crate::config::PidConfig;
+
+/// PID controller for closed-loop fan control
+///
+/// Computes a fan speed percentage from the difference between the measured
+/// temperature and the target temperature. The controller keeps its integral
+/// and the previous error between updates, so the same instance has to be
+/// reused for consecutive measurements of a target.
+///
+/// The output is clamped to the configured fan speed range and the integral
+/// is only accumulated while the output is not saturated (anti-windup).
+#[derive(Debug, Clone, PartialEq)]
+pub struct PidController {
+    config: PidConfig,
+    integral: f32,
+    previous_error: Option<f32>,
+}
+
+impl PidController {
+    /// Creates a new PID controller
+    ///
+    /// # Arguments
+    /// * `config` - Target temperature, gains and output range
+    ///
+    /// # Returns
+    /// * `PidController` - A controller with an empty integral
+    pub fn new(config: PidConfig) -> Self {
+        PidController {
+            config,
+            integral: 0.0,
+            previous_error: None,
+        }
+    }
+
+    /// Feeds a new temperature measurement into the controller
+    ///
+    /// # Arguments
+    /// * `current_temp` - Measured temperature in Celsius
+    /// * `dt` - Seconds elapsed since the previous update (0 for the first one)
+    ///
+    /// # Returns
+    /// * `f32` - Fan speed percentage clamped to `min_fan_speed..=max_fan_speed`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ilo4_fan_control::config::PidConfig;
+    /// use ilo4_fan_control::pid::PidController;
+    ///
+    /// let mut controller = PidController::new(PidConfig {
+    ///     target_temp: 60,
+    ///     kp: 4.0,
+    ///     ki: 0.05,
+    ///     kd: 0.0,
+    ///     min_fan_speed: 10,
+    ///     max_fan_speed: 100,
+    /// });
+    ///
+    /// let speed = controller.update(65.0, 0.0);
+    /// assert_eq!(speed, 20.0);
+    /// ```
+    pub fn update(&mut self, current_temp: f32, dt: f32) -> f32 {
+        let min = self.config.min_fan_speed as f32;
+        let max = self.config.max_fan_speed as f32;
+
+        // Positive error means the temperature is above the target
+        let error = current_temp - self.config.target_temp as f32;
+
+        let proportional = self.config.kp * error;
+        let derivative = match self.previous_error {
+            Some(previous_error) if dt > 0.0 => self.config.kd * (error - previous_error) / dt,
+            _ => 0.0,
+        };
+        self.previous_error = Some(error);
+
+        // Only integrate while the output is not pushed further into saturation
+        let integral = self.integral + error * dt;
+        let unclamped = proportional + self.config.ki * integral + derivative;
+        let winding_up = (unclamped > max && error > 0.0) || (unclamped < min && error < 0.0);
+        if !winding_up {
+            self.integral = integral;
+        }
+
+        (proportional + self.config.ki * self.integral + derivative).clamp(min, max)
+    }
+
+    /// Clears the integral and the previous error
+    pub fn reset(&mut self) {
+        self.integral = 0.0;
+        self.previous_error = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Simple first-order thermal model of a server
+    ///
+    /// A constant heat load warms the chassis while the fans remove heat in
+    /// proportion to their speed and the difference to the ambient temperature.
+    struct ThermalPlant {
+        temp: f32,
+        ambient: f32,
+        heat_load: f32,
+    }
+
+    impl ThermalPlant {
+        fn step(&mut self, fan_speed: f32, dt: f32) {
+            let cooling = 0.0003 * fan_speed * (self.temp - self.ambient);
+            let passive = 0.001 * (self.temp - self.ambient);
+            self.temp += (self.heat_load - cooling - passive) * dt;
+        }
+    }
+
+    fn create_pid_config() -> PidConfig {
+        PidConfig {
+            target_temp: 60,
+            kp: 4.0,
+            ki: 0.05,
+            kd: 1.0,
+            min_fan_speed: 10,
+            max_fan_speed: 100,
+        }
+    }
+
+    fn simulate(
+        controller: &mut PidController,
+        plant: &mut ThermalPlant,
+        steps: usize,
+        dt: f32,
+    ) -> f32 {
+        let mut speed = 0.0;
+        for _ in 0..steps {
+            speed = controller.update(plant.temp, dt);
+            plant.step(speed, dt);
+        }
+        speed
+    }
+
+    #[test]
+    fn test_converges_to_target_temperature() {
+        let mut controller = PidController::new(create_pid_config());
+        let mut plant = ThermalPlant {
+            temp: 40.0,
+            ambient: 25.0,
+            heat_load: 0.5,
+        };
+
+        let speed = simulate(&mut controller, &mut plant, 3000, 1.0);
+
+        assert!(
+            (plant.temp - 60.0).abs() < 0.5,
+            "temperature should settle at the target, got {}",
+            plant.temp
+        );
+        assert!(speed > 10.0 && speed < 100.0);
+    }
+
+    #[test]
+    fn test_follows_load_change() {
+        let mut controller = PidController::new(create_pid_config());
+        let mut plant = ThermalPlant {
+            temp: 60.0,
+            ambient: 25.0,
+            heat_load: 0.3,
+        };
+        let idle_speed = simulate(&mut controller, &mut plant, 3000, 1.0);
+
+        plant.heat_load = 0.6;
+        let loaded_speed = simulate(&mut controller, &mut plant, 3000, 1.0);
+
+        assert!((plant.temp - 60.0).abs() < 0.5);
+        assert!(loaded_speed > idle_speed);
+    }
+
+    #[test]
+    fn test_output_is_clamped() {
+        let mut controller = PidController::new(create_pid_config());
+
+        assert_eq!(controller.update(20.0, 0.0), 10.0);
+        assert_eq!(controller.update(120.0, 1.0), 100.0);
+    }
+
+    #[test]
+    fn test_anti_windup() {
+        let mut config = create_pid_config();
+        config.max_fan_speed = 30;
+        let mut controller = PidController::new(config);
+
+        // Stay far above the target while the output is saturated
+        for _ in 0..1000 {
+            assert_eq!(controller.update(70.0, 1.0), 30.0);
+        }
+
+        // As soon as the temperature falls below the target the output
+        // has to leave saturation instead of unwinding a huge integral
+        let speed = controller.update(59.0, 1.0);
+        assert!(speed < 30.0, "output stayed saturated: {}", speed);
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut controller = PidController::new(create_pid_config());
+        let mut plant = ThermalPlant {
+            temp: 70.0,
+            ambient: 25.0,
+            heat_load: 0.5,
+        };
+        simulate(&mut controller, &mut plant, 100, 1.0);
+
+        controller.reset();
+        assert_eq!(controller, PidController::new(create_pid_config()));
+    }
+}
@@ -0,0 +1,744 @@
+use std::fs::OpenOptions;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine as _;
+use log::{debug, info, warn};
+use reqwest::{Certificate, Client, ClientBuilder, Method, Response, StatusCode};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{DigitallySignedStruct, SignatureScheme};
+use serde_json::json;
+
+use crate::config::{TargetIlo, TlsConfig, TlsMode};
+
+/// Path of the Redfish session collection
+const SESSIONS_PATH: &str = "/redfish/v1/SessionService/Sessions/";
+
+/// Seconds to wait for the TCP and TLS handshake with the iLO
+const CONNECT_TIMEOUT_SECONDS: u64 = 10;
+
+/// Seconds to wait for a complete answer of the iLO
+const REQUEST_TIMEOUT_SECONDS: u64 = 30;
+
+/// Seconds an idle connection to the iLO is kept open for reuse
+const POOL_IDLE_TIMEOUT_SECONDS: u64 = 90;
+
+/// Redfish HTTP client of an iLO
+///
+/// Owns the connection pool, base URL, credentials, timeouts and TLS
+/// settings of one iLO, so TLS handshakes are only paid when the iLO closed
+/// the connection. Keep one client per iLO for as long as it is polled.
+///
+/// Logs in once with `POST /redfish/v1/SessionService/Sessions/` and sends
+/// the returned `X-Auth-Token` with every request, so polling the iLO does
+/// not create a login event each time. An expired session is created again
+/// when the iLO answers with `401 Unauthorized`.
+///
+/// In the `Pin` mode the certificate is compared with the pin during every
+/// TLS handshake, so no request is sent to a peer with another certificate.
+/// Without a configured fingerprint, the certificate of the first handshake
+/// is pinned in the pin file and enforced from then on.
+pub struct RedfishClient {
+    /// HTTP client holding the connection pool
+    client: Client,
+    /// Hostname or IP address of the iLO, optionally with a port
+    address: String,
+    /// URL all paths are relative to, e.g. `https://192.168.1.100`
+    base_url: String,
+    /// User name of the iLO
+    user: String,
+    /// Plain text password of the iLO
+    password: String,
+    /// Token of the current session
+    token: Option<String>,
+    /// URI of the current session, used to delete it
+    location: Option<String>,
+}
+
+impl RedfishClient {
+    /// Creates a client without logging in yet
+    ///
+    /// # Arguments
+    /// * `address` - The hostname or IP address of the iLO
+    /// * `user` - User name of the iLO
+    /// * `password_base64` - Password encoded in base64, or in plain text
+    /// * `tls` - Verification of the iLO's certificate
+    ///
+    /// # Returns
+    /// * `Result<RedfishClient>` - Client or an error if the password is not
+    ///   valid UTF-8 or the HTTP client could not be created
+    pub fn new(address: &str, user: &str, password_base64: &str, tls: &TlsConfig) -> Result<Self> {
+        let password = BASE64_STANDARD
+            .decode(password_base64.as_bytes())
+            .unwrap_or_else(|_| password_base64.as_bytes().to_vec());
+
+        let password = String::from_utf8(password)?;
+
+        // remove \n and \r
+        let password = password
+            .chars()
+            .filter(|&c| c != '\n' && c != '\r')
+            .collect::<String>();
+
+        let client = configure_tls(Client::builder(), address, tls)?
+            .connect_timeout(Duration::from_secs(CONNECT_TIMEOUT_SECONDS))
+            .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECONDS))
+            .pool_idle_timeout(Duration::from_secs(POOL_IDLE_TIMEOUT_SECONDS))
+            .build()?;
+
+        Ok(RedfishClient {
+            client,
+            address: address.to_string(),
+            base_url: format!("https://{}", address),
+            user: user.to_string(),
+            password,
+            token: None,
+            location: None,
+        })
+    }
+
+    /// Creates a client for a target of the configuration
+    ///
+    /// # Arguments
+    /// * `target` - Target iLO from the configuration
+    ///
+    /// # Returns
+    /// * `Result<RedfishClient>` - Client or an error
+    pub fn from_target(target: &TargetIlo) -> Result<Self> {
+        Self::new(
+            &target.host,
+            &target.user,
+            &target.password_base64,
+            &target.tls,
+        )
+    }
+
+    /// Returns the hostname or IP address of the iLO
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Checks whether a session has been created
+    pub fn is_logged_in(&self) -> bool {
+        self.token.is_some()
+    }
+
+    /// Reads a Redfish resource
+    ///
+    /// Logs in first if there is no session yet, and logs in again once if
+    /// the iLO rejects the token of an expired session.
+    ///
+    /// # Arguments
+    /// * `path` - Path of the resource, e.g. `/redfish/v1/Chassis/1/Thermal/`
+    ///
+    /// # Returns
+    /// * `Result<String>` - The response body or an error
+    pub async fn get(&mut self, path: &str) -> Result<String> {
+        if self.token.is_none() {
+            self.login().await?;
+        }
+
+        let mut response = self.send(Method::GET, path).await?;
+        if response.status() == StatusCode::UNAUTHORIZED {
+            info!(
+                "Redfish session on {} expired, logging in again",
+                self.address
+            );
+            self.token = None;
+            self.location = None;
+            self.login().await?;
+            response = self.send(Method::GET, path).await?;
+        }
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(anyhow::anyhow!(
+                "Request to {}{} failed with {}",
+                self.address,
+                path,
+                status
+            ));
+        }
+
+        Ok(response.text().await?)
+    }
+
+    /// Deletes the session on the iLO
+    ///
+    /// Does nothing if there is no session. The session is forgotten even if
+    /// the iLO could not be reached, it expires on the iLO on its own.
+    pub async fn logout(&mut self) -> Result<()> {
+        let Some(location) = self.location.take() else {
+            self.token = None;
+            return Ok(());
+        };
+
+        let response = self.send(Method::DELETE, &location).await;
+        self.token = None;
+
+        let status = response?.status();
+        if !status.is_success() && status != StatusCode::UNAUTHORIZED {
+            return Err(anyhow::anyhow!(
+                "Failed to delete the Redfish session on {}: {}",
+                self.address,
+                status
+            ));
+        }
+
+        debug!("Deleted Redfish session {} on {}", location, self.address);
+        Ok(())
+    }
+
+    /// Creates a session and stores its token and location
+    async fn login(&mut self) -> Result<()> {
+        debug!("Creating Redfish session on {}", self.address);
+
+        let response = self
+            .client
+            .post(self.url(SESSIONS_PATH))
+            .json(&json!({ "UserName": self.user, "Password": self.password }))
+            .send()
+            .await
+            .map_err(request_error)?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(anyhow::anyhow!(
+                "Failed to create a Redfish session on {} as {}: {}",
+                self.address,
+                self.user,
+                status
+            ));
+        }
+
+        let header = |name: &str| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.to_string())
+        };
+
+        let token = header("X-Auth-Token")
+            .ok_or_else(|| anyhow::anyhow!("iLO at {} returned no X-Auth-Token", self.address))?;
+        let location = header("Location");
+        if location.is_none() {
+            warn!(
+                "iLO at {} returned no session location, the session cannot be deleted",
+                self.address
+            );
+        }
+
+        self.token = Some(token);
+        self.location = location;
+        Ok(())
+    }
+
+    /// Sends a request with the session token
+    async fn send(&self, method: Method, path: &str) -> Result<Response> {
+        let mut request = self.client.request(method, self.url(path));
+        if let Some(token) = &self.token {
+            request = request.header("X-Auth-Token", token);
+        }
+        request.send().await.map_err(request_error)
+    }
+
+    /// Returns the URL of a path, keeping absolute URLs as they are
+    fn url(&self, path: &str) -> String {
+        if path.starts_with("https://") || path.starts_with("http://") {
+            path.to_string()
+        } else {
+            format!("{}{}", self.base_url, path)
+        }
+    }
+}
+
+/// Applies the certificate verification of a target to an HTTP client
+fn configure_tls(builder: ClientBuilder, address: &str, tls: &TlsConfig) -> Result<ClientBuilder> {
+    match tls.mode {
+        TlsMode::Verify => {
+            let Some(ca_bundle) = &tls.ca_bundle else {
+                return Ok(builder);
+            };
+            let pem = std::fs::read(ca_bundle)
+                .with_context(|| format!("Failed to read CA bundle {}", ca_bundle))?;
+            let certificates = Certificate::from_pem_bundle(&pem)
+                .with_context(|| format!("Invalid CA bundle {}", ca_bundle))?;
+            Ok(certificates
+                .into_iter()
+                .fold(builder, |builder, certificate| {
+                    builder.add_root_certificate(certificate)
+                }))
+        }
+        TlsMode::Pin => {
+            let (pinned, pin_file) = match (&tls.fingerprint_sha256, &tls.pin_file) {
+                (Some(fingerprint), _) => (Some(parse_fingerprint(fingerprint)?), None),
+                (None, Some(pin_file)) => {
+                    let pin_file = PathBuf::from(pin_file);
+                    (load_pin(&pin_file, address)?, Some(pin_file))
+                }
+                (None, None) => {
+                    return Err(anyhow::anyhow!(
+                        "fingerprint_sha256 or pin_file is required to pin the certificate of {}",
+                        address
+                    ))
+                }
+            };
+            let provider = Arc::new(rustls::crypto::ring::default_provider());
+            let verifier = PinnedCertVerifier {
+                address: address.to_string(),
+                pinned: Mutex::new(pinned),
+                pin_file,
+                provider: provider.clone(),
+            };
+            let config = rustls::ClientConfig::builder_with_provider(provider)
+                .with_safe_default_protocol_versions()?
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(verifier))
+                .with_no_client_auth();
+            Ok(builder.use_preconfigured_tls(config))
+        }
+        TlsMode::Insecure => {
+            warn!(
+                "Certificate verification for {} is disabled, consider the Pin mode",
+                address
+            );
+            Ok(builder.danger_accept_invalid_certs(true))
+        }
+    }
+}
+
+/// Accepts only the certificate with the pinned fingerprint
+///
+/// Runs during the TLS handshake, so a peer with another certificate never
+/// sees a request. The chain and host name are not checked, the pin replaces
+/// them for the self-signed certificate of the iLO.
+#[derive(Debug)]
+struct PinnedCertVerifier {
+    /// Hostname or IP address of the iLO, the key of its pin in `pin_file`
+    address: String,
+    /// Fingerprint of the accepted certificate, `None` until the first use
+    pinned: Mutex<Option<[u8; 32]>>,
+    /// File the certificate of the first use is pinned in
+    pin_file: Option<PathBuf>,
+    /// Provider of the signature algorithms
+    provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for PinnedCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let fingerprint = openssl::sha::sha256(end_entity.as_ref());
+        let mut pinned = self
+            .pinned
+            .lock()
+            .map_err(|_| rustls::Error::General("pin lock poisoned".to_string()))?;
+
+        match *pinned {
+            Some(pinned) if fingerprint != pinned => Err(rustls::Error::General(format!(
+                "certificate of {} does not match the pinned fingerprint, expected {} but got {}",
+                self.address,
+                format_fingerprint(&pinned),
+                format_fingerprint(&fingerprint)
+            ))),
+            Some(_) => Ok(ServerCertVerified::assertion()),
+            None => {
+                // Trust the first certificate only once its pin is stored
+                let pin_file = self.pin_file.as_deref().ok_or_else(|| {
+                    rustls::Error::General(format!("no pin for {}", self.address))
+                })?;
+                store_pin(pin_file, &self.address, &fingerprint).map_err(|e| {
+                    rustls::Error::General(format!(
+                        "failed to pin the certificate of {}: {:#}",
+                        self.address, e
+                    ))
+                })?;
+                info!(
+                    "Pinned the certificate of {} with fingerprint {} in {}",
+                    self.address,
+                    format_fingerprint(&fingerprint),
+                    pin_file.display()
+                );
+                *pinned = Some(fingerprint);
+                Ok(ServerCertVerified::assertion())
+            }
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+/// Reads the pin of an iLO from a pin file
+///
+/// Each line of the file holds the address of an iLO and the fingerprint of
+/// its certificate, separated by whitespace. Empty lines and lines starting
+/// with `#` are skipped.
+///
+/// # Arguments
+/// * `pin_file` - File with the pins
+/// * `address` - Hostname or IP address of the iLO as configured
+///
+/// # Returns
+/// * `Result<Option<[u8; 32]>>` - The pin, `None` if the file or the iLO is
+///   not found, or an error if the file cannot be read or is malformed
+pub fn load_pin(pin_file: &Path, address: &str) -> Result<Option<[u8; 32]>> {
+    let content = match std::fs::read_to_string(pin_file) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(e)
+                .with_context(|| format!("Failed to read pin file {}", pin_file.display()))
+        }
+    };
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        if fields.next() != Some(address) {
+            continue;
+        }
+        let fingerprint = fields.next().ok_or_else(|| {
+            anyhow::anyhow!(
+                "Pin of {} in {} has no fingerprint",
+                address,
+                pin_file.display()
+            )
+        })?;
+        return parse_fingerprint(fingerprint)
+            .map(Some)
+            .with_context(|| format!("Invalid pin of {} in {}", address, pin_file.display()));
+    }
+
+    Ok(None)
+}
+
+/// Appends the pin of an iLO to a pin file, creating the file if needed
+///
+/// # Arguments
+/// * `pin_file` - File with the pins
+/// * `address` - Hostname or IP address of the iLO as configured
+/// * `fingerprint` - SHA-256 fingerprint of its certificate
+pub fn store_pin(pin_file: &Path, address: &str, fingerprint: &[u8; 32]) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(pin_file)
+        .with_context(|| format!("Failed to open pin file {}", pin_file.display()))?;
+    writeln!(file, "{} {}", address, format_fingerprint(fingerprint))
+        .with_context(|| format!("Failed to write pin file {}", pin_file.display()))?;
+    Ok(())
+}
+
+/// Parses a SHA-256 fingerprint
+///
+/// # Arguments
+/// * `fingerprint` - 64 hex digits, optionally separated by colons as
+///   printed by `openssl x509 -fingerprint -sha256`
+///
+/// # Returns
+/// * `Result<[u8; 32]>` - The fingerprint or an error if it is malformed
+pub fn parse_fingerprint(fingerprint: &str) -> Result<[u8; 32]> {
+    let hex: String = fingerprint.chars().filter(|&c| c != ':').collect();
+    if hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(anyhow::anyhow!(
+            "Invalid SHA-256 fingerprint '{}', expected 64 hex digits",
+            fingerprint
+        ));
+    }
+
+    let mut bytes = [0u8; 32];
+    for (index, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16)?;
+    }
+    Ok(bytes)
+}
+
+/// Formats a SHA-256 fingerprint as colon separated hex digits
+pub fn format_fingerprint(fingerprint: &[u8; 32]) -> String {
+    let bytes: Vec<String> = fingerprint
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect();
+    bytes.join(":")
+}
+
+/// Turns a failed request into an error that names its causes
+///
+/// The message of reqwest alone hides TLS errors such as a certificate that
+/// does not match its pin.
+fn request_error(error: reqwest::Error) -> anyhow::Error {
+    let mut message = error.to_string();
+    let mut source = std::error::Error::source(&error);
+    while let Some(cause) = source {
+        let cause_message = cause.to_string();
+        if !message.contains(&cause_message) {
+            message = format!("{}: {}", message, cause_message);
+        }
+        source = cause.source();
+    }
+    anyhow::anyhow!(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_password_decoding() {
+        let client =
+            RedfishClient::new("localhost", "user", "cGFzc3dvcmQK", &TlsConfig::default()).unwrap();
+        assert_eq!(client.password, "password");
+
+        let client =
+            RedfishClient::new("localhost", "user", "pass word", &TlsConfig::default()).unwrap();
+        assert_eq!(client.password, "pass word");
+        assert!(!client.is_logged_in());
+    }
+
+    #[test]
+    fn test_url() {
+        let client =
+            RedfishClient::new("192.168.1.100", "user", "cA==", &TlsConfig::default()).unwrap();
+        assert_eq!(
+            client.url(SESSIONS_PATH),
+            "https://192.168.1.100/redfish/v1/SessionService/Sessions/"
+        );
+        assert_eq!(
+            client.url("https://192.168.1.100/redfish/v1/SessionService/Sessions/admin1/"),
+            "https://192.168.1.100/redfish/v1/SessionService/Sessions/admin1/"
+        );
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let fingerprint = "3A:AE:B6:7A:E6:F9:B1:E5:43:D7:FD:28:6D:39:D6:1D:84:C4:FB:66:A1:2A:51:86:81:31:74:35:CD:30:FA:2B";
+        let bytes = parse_fingerprint(fingerprint).unwrap();
+        assert_eq!(format_fingerprint(&bytes), fingerprint);
+        assert_eq!(
+            parse_fingerprint(&fingerprint.replace(':', "").to_lowercase()).unwrap(),
+            bytes
+        );
+
+        assert!(parse_fingerprint("3A:AE").is_err());
+        assert!(parse_fingerprint(&"G".repeat(64)).is_err());
+    }
+
+    #[test]
+    fn test_pin_file() {
+        let pin_dir = tempfile::tempdir().unwrap();
+        let pin_file = pin_dir.path().join("pins");
+        let fingerprint = [0xAB; 32];
+
+        // No pin yet without the file
+        assert_eq!(load_pin(&pin_file, "192.168.1.100").unwrap(), None);
+
+        store_pin(&pin_file, "192.168.1.100", &fingerprint).unwrap();
+        store_pin(&pin_file, "192.168.1.101", &[0xCD; 32]).unwrap();
+        assert_eq!(
+            load_pin(&pin_file, "192.168.1.100").unwrap(),
+            Some(fingerprint)
+        );
+        assert_eq!(
+            load_pin(&pin_file, "192.168.1.101").unwrap(),
+            Some([0xCD; 32])
+        );
+        assert_eq!(load_pin(&pin_file, "192.168.1.1").unwrap(), None);
+
+        // Malformed pins are errors
+        std::fs::write(&pin_file, "# pins\n\n192.168.1.100 3A:AE\n").unwrap();
+        assert!(load_pin(&pin_file, "192.168.1.100").is_err());
+        std::fs::write(&pin_file, "192.168.1.100\n").unwrap();
+        assert!(load_pin(&pin_file, "192.168.1.100").is_err());
+    }
+
+    #[test]
+    fn test_missing_ca_bundle() {
+        let tls = TlsConfig {
+            ca_bundle: Some("/nonexistent/ca.pem".to_string()),
+            ..Default::default()
+        };
+        let error = RedfishClient::new("localhost", "user", "cA==", &tls)
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("/nonexistent/ca.pem"));
+    }
+
+    /// Test HTTPS server, stopped when dropped so a failed test does not
+    /// leave it running
+    struct TestServer(std::process::Child);
+
+    impl Drop for TestServer {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client() {
+        use std::path::Path;
+        use std::process::Command;
+        use std::thread::sleep;
+        use std::time::Duration;
+
+        // Start the test server as a background process
+        let server_dir = Path::new("./test-https-server");
+        let _server = TestServer(
+            Command::new("python3")
+                .arg("runserver.py")
+                .current_dir(server_dir)
+                .spawn()
+                .expect("Failed to start test server"),
+        );
+
+        // Give the server a moment to start
+        sleep(Duration::from_secs(2));
+
+        let insecure = TlsConfig {
+            mode: TlsMode::Insecure,
+            ..Default::default()
+        };
+        let mut client =
+            RedfishClient::new("localhost:8080", "user", "cGFzc3dvcmQ=", &insecure).unwrap();
+
+        // Logs in on the first request
+        assert_eq!(client.get("/").await.unwrap(), "Hello-World-Test");
+        assert!(client.is_logged_in());
+        let token = client.token.clone();
+
+        // Reuses the session
+        client.get("/").await.unwrap();
+        assert_eq!(client.token, token);
+
+        // Logs in again when the session expired
+        client.token = Some("expired".to_string());
+        assert_eq!(client.get("/").await.unwrap(), "Hello-World-Test");
+        assert_ne!(client.token.as_deref(), Some("expired"));
+
+        // Deletes the session
+        client.logout().await.unwrap();
+        assert!(!client.is_logged_in());
+
+        // Accepts the certificate with the pinned fingerprint
+        let pem = std::fs::read(server_dir.join("localhost.pem")).unwrap();
+        let certificate = openssl::x509::X509::from_pem(&pem).unwrap();
+        let digest = certificate
+            .digest(openssl::hash::MessageDigest::sha256())
+            .unwrap();
+        let pin = |fingerprint: Option<String>| TlsConfig {
+            mode: TlsMode::Pin,
+            fingerprint_sha256: fingerprint,
+            ..Default::default()
+        };
+        let fingerprint = format_fingerprint(&digest.as_ref().try_into().unwrap());
+        let mut client = RedfishClient::new(
+            "localhost:8080",
+            "user",
+            "cA==",
+            &pin(Some(fingerprint.clone())),
+        )
+        .unwrap();
+        assert_eq!(client.get("/").await.unwrap(), "Hello-World-Test");
+        client.logout().await.unwrap();
+
+        // A pin kept only in memory is not allowed
+        assert!(RedfishClient::new("localhost:8080", "user", "cA==", &pin(None)).is_err());
+
+        // Pins the certificate on first use and keeps the pin in the pin file
+        let pin_dir = tempfile::tempdir().unwrap();
+        let pin_file = pin_dir.path().join("pins");
+        let first_use = TlsConfig {
+            pin_file: Some(pin_file.to_string_lossy().to_string()),
+            ..pin(None)
+        };
+        let mut client = RedfishClient::new("localhost:8080", "user", "cA==", &first_use).unwrap();
+        assert_eq!(client.get("/").await.unwrap(), "Hello-World-Test");
+        client.logout().await.unwrap();
+        assert_eq!(
+            load_pin(&pin_file, "localhost:8080").unwrap(),
+            Some(digest.as_ref().try_into().unwrap())
+        );
+
+        // Enforces the stored pin afterwards
+        let mut client = RedfishClient::new("localhost:8080", "user", "cA==", &first_use).unwrap();
+        assert_eq!(client.get("/").await.unwrap(), "Hello-World-Test");
+        client.logout().await.unwrap();
+
+        std::fs::write(&pin_file, format!("localhost:8080 {}\n", "00".repeat(32))).unwrap();
+        let mut client = RedfishClient::new("localhost:8080", "user", "cA==", &first_use).unwrap();
+        let error = client.get("/").await.err().unwrap();
+        assert!(
+            error
+                .to_string()
+                .contains("does not match the pinned fingerprint"),
+            "{}",
+            error
+        );
+        assert!(!client.is_logged_in());
+
+        // Rejects a certificate that does not match the pin
+        let mut client = RedfishClient::new(
+            "localhost:8080",
+            "user",
+            "cA==",
+            &pin(Some("00".repeat(32))),
+        )
+        .unwrap();
+        let error = client.get("/").await.err().unwrap();
+        assert!(
+            error
+                .to_string()
+                .contains("does not match the pinned fingerprint"),
+            "{}",
+            error
+        );
+        assert!(!client.is_logged_in());
+    }
+}
@@ -3,7 +3,14 @@ use base64::prelude::BASE64_STANDARD;
 use base64::Engine as _;
 use log::debug;
 use std::io::Read;
-use std::net::TcpStream;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// Seconds between keepalive messages of an idle SSH session
+const KEEPALIVE_INTERVAL_SECONDS: u32 = 30;
+
+/// Default limit in seconds for connecting and for every blocking SSH call
+const DEFAULT_TIMEOUT_SECONDS: u64 = 30;
 
 /// SSH client for ILO connection
 ///
@@ -14,6 +21,7 @@ pub struct SshClient {
     user: String,
     password: String,
     session: Option<ssh2::Session>,
+    timeout: Duration,
 }
 
 impl SshClient {
@@ -45,9 +53,22 @@ impl SshClient {
             user,
             password,
             session: None,
+            timeout: Duration::from_secs(DEFAULT_TIMEOUT_SECONDS),
         }
     }
 
+    /// Sets the limit for connecting and for every blocking SSH call
+    ///
+    /// A half-open connection to an unresponsive iLO fails with an error
+    /// after this time instead of blocking forever. Takes effect with the
+    /// next connection.
+    ///
+    /// # Arguments
+    /// * `timeout` - Time limit, at least one millisecond
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout.max(Duration::from_millis(1));
+    }
+
     /// Establishes an SSH connection to the ILO interface
     ///
     /// # Returns
@@ -56,7 +77,7 @@ impl SshClient {
     /// This method configures the SSH session with legacy algorithm support
     /// required for ILO interfaces and establishes the connection.
     pub fn connect(&mut self) -> Result<()> {
-        let tcp = TcpStream::connect(format!("{}:22", self.host))
+        let tcp = connect_timeout(format!("{}:22", self.host), self.timeout)
             .with_context(|| format!("SSH接続に失敗しました: {}", self.host))?;
 
         self.session =
@@ -84,6 +105,9 @@ impl SshClient {
             "aes128-ctr,aes192-ctr,aes256-ctr,aes128-cbc,3des-cbc,aes192-cbc,aes256-cbc",
         )?;
 
+        // Fail blocking calls on a dead connection instead of hanging
+        session.set_timeout(self.timeout.as_millis().min(u32::MAX as u128) as u32);
+
         session.set_tcp_stream(tcp);
         session.handshake()?;
 
@@ -95,9 +119,61 @@ impl SshClient {
         debug!("Attempting to authenticate user: {}", user);
         session.userauth_password(user, password)?;
 
+        // Let the server know the session is still in use between commands
+        session.set_keepalive(true, KEEPALIVE_INTERVAL_SECONDS);
+
         Ok(())
     }
 
+    /// Checks whether the SSH session is still usable
+    ///
+    /// # Returns
+    /// * `bool` - `true` if the session is authenticated and the server
+    ///   still accepts keepalive messages
+    pub fn is_connected(&self) -> bool {
+        match &self.session {
+            Some(session) => session.authenticated() && session.keepalive_send().is_ok(),
+            None => false,
+        }
+    }
+
+    /// Closes the SSH session
+    pub fn disconnect(&mut self) {
+        if let Some(session) = self.session.take() {
+            if let Err(e) = session.disconnect(None, "Closing session", None) {
+                debug!("Failed to close SSH session to {}: {}", self.host, e);
+            }
+        }
+    }
+
+    /// Executes commands, connecting or reconnecting first when needed
+    ///
+    /// # Arguments
+    /// * `commands` - List of commands to execute
+    ///
+    /// # Returns
+    /// * `Result<Vec<String>>` - Output of each command or an error
+    ///
+    /// The session is kept open for the next call. When it turns out to be
+    /// dead while executing, the commands are sent once more over a new
+    /// session, so they have to be safe to repeat.
+    pub fn exec_with_reconnect(&mut self, commands: Vec<String>) -> Result<Vec<String>> {
+        if !self.is_connected() {
+            debug!("Opening SSH session to {}", self.host);
+            self.connect()?;
+        }
+
+        match self.exec(commands.clone()) {
+            Ok(output) => Ok(output),
+            Err(e) => {
+                debug!("SSH session to {} failed, reconnecting: {}", self.host, e);
+                self.disconnect();
+                self.connect()?;
+                self.exec(commands)
+            }
+        }
+    }
+
     /// Executes commands over the SSH connection
     ///
     /// # Arguments
@@ -110,7 +186,11 @@ impl SshClient {
     pub fn exec(&mut self, commands: Vec<String>) -> Result<Vec<String>> {
         let mut result = Vec::new();
         for command in commands {
-            let mut channel = self.session.as_mut().unwrap().channel_session()?;
+            let mut channel = self
+                .session
+                .as_mut()
+                .with_context(|| format!("SSH session to {} is not connected", self.host))?
+                .channel_session()?;
             channel.exec(&command)?;
 
             let mut s = String::new();
@@ -121,6 +201,37 @@ impl SshClient {
     }
 }
 
+/// Opens a TCP connection, trying every resolved address in turn
+///
+/// Like `TcpStream::connect`, but every attempt is limited by `timeout`, so
+/// a host resolving to an unreachable IPv6 address or to several A records
+/// still connects through the next address.
+///
+/// # Arguments
+/// * `address` - Host and port to connect to
+/// * `timeout` - Time limit of each attempt
+///
+/// # Returns
+/// * `Result<TcpStream>` - The connection, or the error of the last address
+///   if every address failed
+fn connect_timeout(address: impl ToSocketAddrs, timeout: Duration) -> Result<TcpStream> {
+    let mut last_error = None;
+    for address in address.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&address, timeout) {
+            Ok(tcp) => return Ok(tcp),
+            Err(e) => {
+                debug!("Connection to {} failed: {}", address, e);
+                last_error = Some(e);
+            }
+        }
+    }
+
+    Err(match last_error {
+        Some(e) => e.into(),
+        None => anyhow::anyhow!("Host resolved to no address"),
+    })
+}
+
 #[cfg(test)]
 mod test {
     use base64::{engine::general_purpose::STANDARD, Engine as _};
@@ -148,4 +259,70 @@ mod test {
         assert_eq!(results[0].trim(), "hello");
         assert_eq!(results[1].trim(), "world");
     }
+
+    #[test]
+    fn test_ssh_not_connected() {
+        let mut client = super::SshClient::new(
+            "localhost".to_string(),
+            "test".to_string(),
+            STANDARD.encode("password".as_bytes()),
+        );
+        assert!(!client.is_connected());
+        assert!(client.exec(vec!["echo hello".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_ssh_timeout() {
+        // 応答しないホストへの接続はタイムアウトで失敗する
+        let mut client = super::SshClient::new(
+            "10.255.255.1".to_string(),
+            "test".to_string(),
+            STANDARD.encode("password".as_bytes()),
+        );
+        client.set_timeout(std::time::Duration::from_millis(200));
+
+        let started = std::time::Instant::now();
+        assert!(client.connect().is_err());
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_connect_timeout_tries_every_address() {
+        // 最初のアドレスに接続できなくても次のアドレスを試す
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let open = listener.local_addr().unwrap();
+        let closed = {
+            let unused = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            unused.local_addr().unwrap()
+        };
+        let timeout = std::time::Duration::from_secs(1);
+
+        assert!(super::connect_timeout(&[closed, open][..], timeout).is_ok());
+
+        // すべて失敗した場合はエラーを返す
+        assert!(super::connect_timeout(&[closed][..], timeout).is_err());
+        assert!(super::connect_timeout(&[][..] as &[std::net::SocketAddr], timeout).is_err());
+    }
+
+    #[test]
+    #[ignore = "needs a local sshd with the test user"]
+    fn test_ssh_reconnect() {
+        let ssh_user = "test".to_string();
+        let ssh_password = STANDARD.encode("password".as_bytes());
+
+        let mut client = super::SshClient::new("localhost".to_string(), ssh_user, ssh_password);
+        let results = client
+            .exec_with_reconnect(vec!["echo hello".to_string()])
+            .expect("Failed to execute commands");
+        assert_eq!(results[0].trim(), "hello");
+        assert!(client.is_connected());
+
+        // A closed session is opened again
+        client.disconnect();
+        assert!(!client.is_connected());
+        let results = client
+            .exec_with_reconnect(vec!["echo world".to_string()])
+            .expect("Failed to execute commands");
+        assert_eq!(results[0].trim(), "world");
+    }
 }
@@ -0,0 +1,466 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::config::{ControlMode, FanConfig, TargetIlo};
+use crate::cputemp::TempData;
+use crate::gen_ssh::FanSpeed;
+
+/// Status of a target as seen by the daemon
+#[derive(Serialize, Debug)]
+pub struct TargetStatus {
+    host: String,
+    /// Error while reading the target, if any
+    error: Option<String>,
+    critical_components: Vec<String>,
+    failed_fans: Vec<String>,
+    /// Sensors that reached a threshold or report a problem
+    warning_sensors: Vec<String>,
+    groups: Vec<GroupStatus>,
+}
+
+/// Status of a fan group of a target
+#[derive(Serialize, Debug)]
+pub struct GroupStatus {
+    /// Fan numbers as used by `fan p N`
+    fans: Vec<u8>,
+    control_mode: ControlMode,
+    /// Highest temperature of the sensors of the group's primary curve
+    temperature: Option<u8>,
+    /// Temperature band the primary curve is in
+    band: Option<FanConfig>,
+    /// Fan speed the daemon would apply to the group's fans
+    fan_speed: Option<FanSpeed>,
+}
+
+impl TargetStatus {
+    /// Builds the status of a target from its readings
+    ///
+    /// # Arguments
+    /// * `target` - Target ILO configuration
+    /// * `temp_data` - Readings of the target
+    pub fn new(target: &TargetIlo, temp_data: &TempData) -> Self {
+        // A fresh state, so hysteresis and PID history of the daemon are not known
+        let (speeds, error) =
+            match crate::gen_ssh::calculate_fan_speeds(target, temp_data, &mut Vec::new()) {
+                Ok(speeds) => (speeds, None),
+                Err(e) => (BTreeMap::new(), Some(e.to_string())),
+            };
+
+        let groups = target
+            .fan_groups()
+            .iter()
+            .map(|group| {
+                let fans = crate::gen_ssh::fan_numbers(&group.target_fans);
+                let temperature = temp_data.max_temperature(&group.curve.sensors);
+                let band = temperature
+                    .and_then(|temp| crate::gen_ssh::current_band(&group.curve, temp))
+                    .cloned();
+                let fan_speed = fans
+                    .iter()
+                    .filter_map(|fan_number| speeds.get(fan_number).copied())
+                    .reduce(FanSpeed::combine);
+
+                GroupStatus {
+                    fans,
+                    control_mode: group.curve.control_mode,
+                    temperature,
+                    band,
+                    fan_speed,
+                }
+            })
+            .collect();
+
+        TargetStatus {
+            host: target.host.clone(),
+            error,
+            critical_components: temp_data.high_temp_component_name.clone(),
+            failed_fans: temp_data
+                .failed_fans()
+                .iter()
+                .map(|fan| fan.name.clone())
+                .collect(),
+            warning_sensors: temp_data
+                .sensors_needing_attention()
+                .iter()
+                .map(|sensor| sensor.name.clone())
+                .collect(),
+            groups,
+        }
+    }
+
+    /// Builds the status of a target that could not be read
+    pub fn failed(target: &TargetIlo, error: anyhow::Error) -> Self {
+        TargetStatus {
+            host: target.host.clone(),
+            error: Some(error.to_string()),
+            critical_components: vec![],
+            failed_fans: vec![],
+            warning_sensors: vec![],
+            groups: vec![],
+        }
+    }
+
+    /// Short summary of the health of the target
+    pub fn summary(&self) -> String {
+        if let Some(error) = &self.error {
+            format!("ERROR: {}", error)
+        } else if !self.critical_components.is_empty() {
+            format!("CRITICAL: {}", self.critical_components.join(", "))
+        } else if !self.failed_fans.is_empty() {
+            format!("Failed fans: {}", self.failed_fans.join(", "))
+        } else if !self.warning_sensors.is_empty() {
+            format!("Attention: {}", self.warning_sensors.join(", "))
+        } else {
+            "OK".to_string()
+        }
+    }
+}
+
+impl GroupStatus {
+    fn fans_text(&self) -> String {
+        let fans: Vec<String> = self.fans.iter().map(|fan| fan.to_string()).collect();
+        fans.join(",")
+    }
+
+    fn band_text(&self) -> String {
+        match (&self.band, self.control_mode) {
+            (_, ControlMode::Pid) => "PID".to_string(),
+            (Some(band), _) => format!("{}-{}°C", band.min_temp, band.max_temp),
+            (None, _) => "-".to_string(),
+        }
+    }
+
+    fn fan_speed_text(&self) -> String {
+        match self.fan_speed {
+            Some(FanSpeed {
+                max,
+                min: Some(min),
+            }) => format!("{:.0}%-{:.0}%", min, max),
+            Some(FanSpeed { max, min: None }) => format!("max {:.0}%", max),
+            None => "-".to_string(),
+        }
+    }
+}
+
+/// Renders the combined status of all targets as a table
+pub fn status_table(statuses: &[TargetStatus]) -> String {
+    let mut table = format!(
+        "{:<24} {:<16} {:>5} {:<10} {:<10} Status\n",
+        "Host", "Fans", "Temp", "Band", "Speed"
+    );
+
+    for status in statuses {
+        if status.groups.is_empty() {
+            table += &format!(
+                "{:<24} {:<16} {:>5} {:<10} {:<10} {}\n",
+                status.host,
+                "-",
+                "-",
+                "-",
+                "-",
+                status.summary()
+            );
+        }
+        for group in &status.groups {
+            let temperature = group
+                .temperature
+                .map(|temp| format!("{}°C", temp))
+                .unwrap_or_else(|| "-".to_string());
+            table += &format!(
+                "{:<24} {:<16} {:>5} {:<10} {:<10} {}\n",
+                status.host,
+                group.fans_text(),
+                temperature,
+                group.band_text(),
+                group.fan_speed_text(),
+                status.summary()
+            );
+        }
+    }
+
+    table
+}
+
+/// Renders the combined status of all targets as CSV, one row per fan group
+pub fn status_csv(statuses: &[TargetStatus]) -> Result<String> {
+    #[derive(Serialize)]
+    struct Row<'a> {
+        host: &'a str,
+        fans: String,
+        temperature: Option<u8>,
+        band: String,
+        max_fan_speed: Option<f32>,
+        min_fan_speed: Option<f32>,
+        status: String,
+    }
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for status in statuses {
+        for group in &status.groups {
+            writer.serialize(Row {
+                host: &status.host,
+                fans: group.fans_text(),
+                temperature: group.temperature,
+                band: group.band_text(),
+                max_fan_speed: group.fan_speed.map(|speed| speed.max),
+                min_fan_speed: group.fan_speed.and_then(|speed| speed.min),
+                status: status.summary(),
+            })?;
+        }
+        if status.groups.is_empty() {
+            writer.serialize(Row {
+                host: &status.host,
+                fans: String::new(),
+                temperature: None,
+                band: String::new(),
+                max_fan_speed: None,
+                min_fan_speed: None,
+                status: status.summary(),
+            })?;
+        }
+    }
+
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+/// Range of a reading seen while watching
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SeenReading {
+    /// Value of the previous sample
+    pub last: u8,
+    /// Lowest value of the session
+    pub min: u8,
+    /// Highest value of the session
+    pub max: u8,
+}
+
+/// Readings seen during a watch session, by reading name
+#[derive(Debug, Default)]
+pub struct SeenReadings {
+    readings: HashMap<String, SeenReading>,
+}
+
+impl SeenReadings {
+    /// Records a new sample of a reading
+    ///
+    /// The first sample of a reading counts as unchanged.
+    ///
+    /// # Arguments
+    /// * `name` - Name of the sensor or fan
+    /// * `current` - Value of the new sample
+    ///
+    /// # Returns
+    /// * `(i16, SeenReading)` - Change since the previous sample, and the
+    ///   range of the reading including the new sample
+    pub fn update(&mut self, name: &str, current: u8) -> (i16, SeenReading) {
+        let reading = self
+            .readings
+            .entry(name.to_string())
+            .or_insert(SeenReading {
+                last: current,
+                min: current,
+                max: current,
+            });
+        let change = current as i16 - reading.last as i16;
+        reading.last = current;
+        reading.min = reading.min.min(current);
+        reading.max = reading.max.max(current);
+
+        (change, *reading)
+    }
+}
+
+/// Formats the change of a reading since the previous sample
+///
+/// # Returns
+/// * `String` - An arrow with the signed change, or an empty string when the
+///   reading did not change
+pub fn change_text(change: i16) -> String {
+    match change {
+        0 => String::new(),
+        change if change > 0 => format!("▲ {:+}", change),
+        change => format!("▼ {:+}", change),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SensorSelector;
+    use crate::cputemp::test::ILO_JSON;
+    use crate::thermal::Thermal;
+
+    const TARGET_TOML: &str = r#"
+host = "192.168.1.100"
+user = "admin"
+password_base64 = "cGFzc3dvcmQ="
+target_fans = { NumFans = 2 }
+
+[[temperature_fan_config]]
+min_temp = 0
+max_temp = 40
+max_fan_speed = 30
+
+[[temperature_fan_config]]
+min_temp = 40
+max_temp = 100
+max_fan_speed = 60
+min_fan_speed = 20
+
+[[fan_groups]]
+target_fans = { TargetFans = [3] }
+sensors = [{ Name = "Chipset" }]
+control_mode = "Pid"
+
+[fan_groups.pid]
+target_temp = 50
+kp = 2.0
+ki = 0.0
+kd = 0.0
+min_fan_speed = 20
+max_fan_speed = 80
+"#;
+
+    fn create_temp_data() -> TempData {
+        let thermal: Thermal = serde_json::from_str(ILO_JSON).unwrap();
+        TempData::from(&thermal)
+    }
+
+    fn create_statuses() -> Vec<TargetStatus> {
+        let target: TargetIlo = toml::from_str(TARGET_TOML).unwrap();
+        let mut unreachable = target.clone();
+        unreachable.host = "192.168.1.101".to_string();
+
+        vec![
+            TargetStatus::new(&target, &create_temp_data()),
+            TargetStatus::failed(&unreachable, anyhow::anyhow!("connection refused")),
+        ]
+    }
+
+    #[test]
+    fn test_target_status_new() {
+        // CPU の最高温度 47°C は 40-100°C の帯、Chipset は PID で制御される
+        let statuses = create_statuses();
+        let status = &statuses[0];
+        assert_eq!(status.error, None);
+        assert_eq!(status.summary(), "OK");
+        assert_eq!(status.groups.len(), 2);
+
+        let cpu = &status.groups[0];
+        assert_eq!(cpu.fans, vec![0, 1]);
+        assert_eq!(cpu.temperature, Some(47));
+        assert_eq!(cpu.band.as_ref().map(|band| band.min_temp), Some(40));
+        assert_eq!(
+            cpu.fan_speed,
+            Some(FanSpeed {
+                max: 60.0,
+                min: Some(20.0)
+            })
+        );
+
+        let chipset = &status.groups[1];
+        assert_eq!(chipset.fans, vec![2]);
+        assert_eq!(chipset.temperature, Some(47));
+        assert_eq!(chipset.band, None);
+        assert_eq!(chipset.band_text(), "PID");
+        // 目標温度を下回るので PID の下限で回る
+        assert_eq!(chipset.fan_speed_text(), "max 20%");
+
+        assert_eq!(statuses[1].summary(), "ERROR: connection refused");
+    }
+
+    #[test]
+    fn test_target_status_unmatched_sensor() {
+        // どのセンサーにも一致しない曲線はエラーとして表示する
+        let mut target: TargetIlo = toml::from_str(TARGET_TOML).unwrap();
+        target.fan_groups[0].curve.sensors = vec![SensorSelector::Name("Missing".to_string())];
+
+        let status = TargetStatus::new(&target, &create_temp_data());
+        assert!(status
+            .summary()
+            .starts_with("ERROR: No sensor of 192.168.1.100"));
+        assert!(status.groups.iter().all(|group| group.fan_speed.is_none()));
+    }
+
+    #[test]
+    fn test_status_table() {
+        let table = status_table(&create_statuses());
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "Host                     Fans              Temp Band       Speed      Status",
+                "192.168.1.100            0,1               47°C 40-100°C   20%-60%    OK",
+                "192.168.1.100            2                 47°C PID        max 20%    OK",
+                "192.168.1.101            -                    - -          -          ERROR: connection refused",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_status_csv() {
+        let csv = status_csv(&create_statuses()).unwrap();
+        assert_eq!(
+            csv,
+            "host,fans,temperature,band,max_fan_speed,min_fan_speed,status\n\
+             192.168.1.100,\"0,1\",47,40-100°C,60.0,20.0,OK\n\
+             192.168.1.100,2,47,PID,20.0,,OK\n\
+             192.168.1.101,,,,,,ERROR: connection refused\n"
+        );
+    }
+
+    #[test]
+    fn test_seen_readings_range() {
+        // 最初のサンプルは変化なし、以降は前回との差と最小・最大を追跡する
+        let mut seen = SeenReadings::default();
+        assert_eq!(
+            seen.update("02-CPU 1", 40),
+            (
+                0,
+                SeenReading {
+                    last: 40,
+                    min: 40,
+                    max: 40
+                }
+            )
+        );
+        assert_eq!(seen.update("02-CPU 1", 45).0, 5);
+        assert_eq!(
+            seen.update("02-CPU 1", 38),
+            (
+                -7,
+                SeenReading {
+                    last: 38,
+                    min: 38,
+                    max: 45
+                }
+            )
+        );
+        assert_eq!(seen.update("02-CPU 1", 38).0, 0);
+
+        // 読み取りごとに別々に追跡する
+        assert_eq!(
+            seen.update("Fan 1", 255),
+            (
+                0,
+                SeenReading {
+                    last: 255,
+                    min: 255,
+                    max: 255
+                }
+            )
+        );
+        assert_eq!(seen.update("Fan 1", 0).0, -255);
+        assert_eq!(seen.update("02-CPU 1", 39).1.max, 45);
+    }
+
+    #[test]
+    fn test_change_text() {
+        // 上昇は ▲、下降は ▼、変化なしは空
+        assert_eq!(change_text(0), "");
+        assert_eq!(change_text(3), "▲ +3");
+        assert_eq!(change_text(-12), "▼ -12");
+    }
+}
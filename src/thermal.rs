@@ -0,0 +1,226 @@
+use serde::{Deserialize, Serialize};
+
+/// Redfish `Thermal` resource of a chassis
+///
+/// Models `/redfish/v1/Chassis/1/Thermal/` as returned by iLO4, including the
+/// HP OEM extensions. Every field is optional, so a sensor with a missing
+/// threshold or a `null` reading does not reject the whole payload.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Thermal {
+    /// URI of the resource
+    #[serde(rename = "@odata.id")]
+    pub odata_id: Option<String>,
+    /// Identifier of the resource (`Thermal`)
+    pub id: Option<String>,
+    /// Name of the resource
+    pub name: Option<String>,
+    /// Fans of the chassis
+    #[serde(default)]
+    pub fans: Vec<Fan>,
+    /// Temperature sensors of the chassis
+    #[serde(default)]
+    pub temperatures: Vec<Temperature>,
+}
+
+/// Health and state of a sensor or fan
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Status {
+    /// Health such as `OK`, `Warning` or `Critical`
+    pub health: Option<String>,
+    /// State such as `Enabled` or `Absent`
+    pub state: Option<String>,
+}
+
+/// Temperature sensor of the `Thermal` resource
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Temperature {
+    /// Sensor name such as `02-CPU 1`
+    pub name: Option<String>,
+    /// Sensor number
+    pub number: Option<u32>,
+    /// Current temperature as reported by iLO4
+    pub current_reading: Option<f64>,
+    /// Current temperature in Celsius as defined by the Redfish schema
+    pub reading_celsius: Option<f64>,
+    /// Area of the sensor such as `CPU`, `Intake` or `SystemBoard`
+    pub physical_context: Option<String>,
+    /// Unit of the readings
+    pub units: Option<String>,
+    /// Health and state of the sensor
+    #[serde(default)]
+    pub status: Status,
+    /// Temperature above which the component needs attention
+    pub upper_threshold_non_critical: Option<f64>,
+    /// Temperature at which the iLO reports the component as critical
+    pub upper_threshold_critical: Option<f64>,
+    /// Temperature at which the iLO shuts the server down
+    pub upper_threshold_fatal: Option<f64>,
+    /// Temperature threshold set by the user
+    pub upper_threshold_user: Option<f64>,
+    /// HP OEM extension
+    pub oem: Option<TemperatureOem>,
+}
+
+/// OEM extension of a temperature sensor
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TemperatureOem {
+    /// Sea of sensors data (`Hpe` on newer firmware)
+    #[serde(rename = "Hp", alias = "Hpe")]
+    pub hp: Option<HpSeaOfSensors>,
+}
+
+/// HP sea of sensors data of a temperature sensor
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct HpSeaOfSensors {
+    /// Horizontal position of the sensor on the system board in millimetres
+    pub location_xmm: Option<i32>,
+    /// Vertical position of the sensor on the system board in millimetres
+    pub location_ymm: Option<i32>,
+    /// Version of the OEM type
+    #[serde(rename = "Type")]
+    pub oem_type: Option<String>,
+}
+
+/// Fan of the `Thermal` resource
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Fan {
+    /// Fan name such as `Fan 1` (`Name` in newer Redfish schemas)
+    #[serde(alias = "Name")]
+    pub fan_name: Option<String>,
+    /// Current fan speed (`Reading` in newer Redfish schemas)
+    #[serde(alias = "Reading")]
+    pub current_reading: Option<f64>,
+    /// Unit of the reading, `Percent` on iLO4
+    #[serde(alias = "ReadingUnits")]
+    pub units: Option<String>,
+    /// Health and state of the fan
+    #[serde(default)]
+    pub status: Status,
+    /// HP OEM extension
+    pub oem: Option<FanOem>,
+}
+
+/// OEM extension of a fan
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FanOem {
+    /// Server fan data (`Hpe` on newer firmware)
+    #[serde(rename = "Hp", alias = "Hpe")]
+    pub hp: Option<HpServerFan>,
+}
+
+/// HP server fan data of a fan
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct HpServerFan {
+    /// Location of the fan such as `System`
+    pub location: Option<String>,
+    /// Version of the OEM type
+    #[serde(rename = "Type")]
+    pub oem_type: Option<String>,
+}
+
+impl Status {
+    /// Returns the health, or the state when no health is reported
+    pub fn summary(&self) -> &str {
+        self.health
+            .as_deref()
+            .or(self.state.as_deref())
+            .unwrap_or("Unknown")
+    }
+}
+
+impl Temperature {
+    /// Returns the current temperature in Celsius
+    ///
+    /// Prefers `ReadingCelsius` of the Redfish schema and falls back to the
+    /// iLO4 specific `CurrentReading`.
+    pub fn reading(&self) -> Option<f64> {
+        self.reading_celsius.or(self.current_reading)
+    }
+
+    /// Returns the health of the sensor, or its state when no health is reported
+    pub fn health(&self) -> &str {
+        self.status.summary()
+    }
+
+    /// Checks whether the sensor is above its critical threshold
+    ///
+    /// A threshold of 0 means the sensor has none. Without a critical
+    /// threshold the fatal threshold is used.
+    pub fn is_critical(&self) -> bool {
+        let threshold = self
+            .upper_threshold_critical
+            .filter(|threshold| *threshold > 0.0)
+            .or(self
+                .upper_threshold_fatal
+                .filter(|threshold| *threshold > 0.0));
+
+        match (self.reading(), threshold) {
+            (Some(reading), Some(threshold)) => reading > threshold,
+            _ => false,
+        }
+    }
+}
+
+impl Fan {
+    /// Returns the health of the fan, or its state when no health is reported
+    pub fn health(&self) -> &str {
+        self.status.summary()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tolerant_fields() {
+        let thermal: Thermal = serde_json::from_str(
+            r#"{
+                "Fans": [
+                    {"FanName": "Fan 1", "CurrentReading": null, "Status": {"State": "Absent"}},
+                    {"Name": "Fan 2", "Reading": 35.5, "ReadingUnits": "Percent"}
+                ],
+                "Temperatures": [
+                    {"Name": "01-Inlet Ambient", "CurrentReading": 23, "PhysicalContext": "Intake"},
+                    {"Name": "02-CPU 1", "CurrentReading": null, "UpperThresholdCritical": 70},
+                    {
+                        "Name": "03-CPU 2",
+                        "ReadingCelsius": 72.5,
+                        "UpperThresholdCritical": 0,
+                        "UpperThresholdFatal": 70,
+                        "Oem": {"Hpe": {"LocationXmm": 11, "LocationYmm": 5}}
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(thermal.fans[0].current_reading, None);
+        assert_eq!(thermal.fans[0].health(), "Absent");
+        assert_eq!(thermal.fans[1].fan_name.as_deref(), Some("Fan 2"));
+        assert_eq!(thermal.fans[1].current_reading, Some(35.5));
+        assert_eq!(thermal.fans[1].health(), "Unknown");
+
+        let temperatures = &thermal.temperatures;
+        assert_eq!(temperatures[0].upper_threshold_critical, None);
+        assert!(!temperatures[0].is_critical());
+        assert_eq!(temperatures[1].reading(), None);
+        assert!(!temperatures[1].is_critical());
+        assert_eq!(temperatures[2].reading(), Some(72.5));
+        assert!(temperatures[2].is_critical());
+        assert_eq!(
+            temperatures[2]
+                .oem
+                .as_ref()
+                .and_then(|oem| oem.hp.as_ref())
+                .and_then(|hp| hp.location_xmm),
+            Some(11)
+        );
+    }
+}
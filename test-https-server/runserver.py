@@ -1,10 +1,56 @@
 import ssl
+import uuid
 from http.server import HTTPServer, SimpleHTTPRequestHandler
 
 PORT = 8080
 CERTFILE = "./localhost.pem"
+SESSIONS_PATH = "/redfish/v1/SessionService/Sessions/"
+
+# Tokens of the Redfish sessions created by the clients
+sessions = {}
+
+
+class Handler(SimpleHTTPRequestHandler):
+    def authorized(self):
+        # Requests without a token are served, unknown tokens are rejected
+        token = self.headers.get("X-Auth-Token")
+        return token is None or token in sessions.values()
+
+    def reply(self, status, headers=None):
+        self.send_response(status)
+        for name, value in (headers or {}).items():
+            self.send_header(name, value)
+        self.send_header("Content-Length", "0")
+        self.end_headers()
+
+    def do_GET(self):
+        if not self.authorized():
+            return self.reply(401)
+        super().do_GET()
+
+    def do_POST(self):
+        length = int(self.headers.get("Content-Length", 0))
+        self.rfile.read(length)
+        if self.path != SESSIONS_PATH:
+            return self.reply(404)
+
+        session_id = uuid.uuid4().hex
+        sessions[session_id] = uuid.uuid4().hex
+        self.reply(
+            201,
+            {
+                "X-Auth-Token": sessions[session_id],
+                "Location": f"https://localhost:{PORT}{SESSIONS_PATH}{session_id}/",
+            },
+        )
+
+    def do_DELETE(self):
+        session_id = self.path.removeprefix(SESSIONS_PATH).strip("/")
+        if not self.authorized() or session_id not in sessions:
+            return self.reply(401)
+        del sessions[session_id]
+        self.reply(200)
 
-Handler = SimpleHTTPRequestHandler
 
 context = ssl.SSLContext(ssl.PROTOCOL_TLS_SERVER)
 context.load_cert_chain(CERTFILE)
@@ -12,4 +58,4 @@ context.load_cert_chain(CERTFILE)
 with HTTPServer(("127.0.0.1", PORT), Handler) as httpd:
     print("serving at address", httpd.server_address, "using cert file", CERTFILE)
     httpd.socket = context.wrap_socket(httpd.socket, server_side=True)
-    httpd.serve_forever()
\ No newline at end of file
+    httpd.serve_forever()